@@ -0,0 +1,224 @@
+use std::io::Cursor;
+
+use image::{AnimationDecoder, ImageFormat};
+
+// Decoded animation with full canvas frames (disposal and blending already applied)
+#[derive(Clone)]
+pub struct Animation {
+    pub frames: Vec<AnimationFrame>,
+    pub plays: u32, // Number of times to play, 0 = loop forever
+}
+
+#[derive(Clone)]
+pub struct AnimationFrame {
+    pub image: image::RgbaImage,
+    pub delay_ms: u32,
+}
+
+impl Animation {
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.frames
+            .first()
+            .map_or((0, 0), |frame| frame.image.dimensions())
+    }
+}
+
+// Number of frames in GIF / APNG / WebP data (1 for still images and other formats)
+// Reads container metadata only, frames are not decoded
+pub fn count_frames(data: &[u8]) -> u32 {
+    container_info(data).map_or(1, |(frames, _)| frames.max(1))
+}
+
+// Decode every frame of an animated GIF / APNG / WebP
+// Returns None for still images and other formats
+pub fn decode_animation(data: &[u8]) -> Result<Option<Animation>, String> {
+    let (frame_count, plays) = match container_info(data) {
+        Some(info) => info,
+        None => return Ok(None),
+    };
+    if frame_count < 2 {
+        return Ok(None);
+    }
+
+    let frames = match image::guess_format(data) {
+        Ok(ImageFormat::Gif) => image::codecs::gif::GifDecoder::new(Cursor::new(data))
+            .map_err(|e| format!("Failed to decode GIF: {}", e))?
+            .into_frames()
+            .collect_frames(),
+        Ok(ImageFormat::Png) => image::codecs::png::PngDecoder::new(Cursor::new(data))
+            .and_then(|decoder| decoder.apng())
+            .map_err(|e| format!("Failed to decode APNG: {}", e))?
+            .into_frames()
+            .collect_frames(),
+        Ok(ImageFormat::WebP) => image::codecs::webp::WebPDecoder::new(Cursor::new(data))
+            .map_err(|e| format!("Failed to decode WebP: {}", e))?
+            .into_frames()
+            .collect_frames(),
+        _ => return Ok(None),
+    }
+    .map_err(|e| format!("Failed to decode animation frames: {}", e))?;
+
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            AnimationFrame {
+                delay_ms: numer / denom.max(1),
+                image: frame.into_buffer(),
+            }
+        })
+        .collect();
+
+    Ok(Some(Animation { frames, plays }))
+}
+
+// Frame count and play count from container metadata
+fn container_info(data: &[u8]) -> Option<(u32, u32)> {
+    match image::guess_format(data).ok()? {
+        ImageFormat::Gif => gif_info(data),
+        ImageFormat::Png => apng_info(data),
+        ImageFormat::WebP => webp_info(data),
+        _ => None,
+    }
+}
+
+// GIF: count frames without LZW decoding, loop count from NETSCAPE extension
+fn gif_info(data: &[u8]) -> Option<(u32, u32)> {
+    let mut options = gif::DecodeOptions::new();
+    options.skip_frame_decoding(true);
+    let mut decoder = options.read_info(Cursor::new(data)).ok()?;
+
+    let mut frames = 0;
+    while let Ok(Some(_)) = decoder.read_next_frame() {
+        frames += 1;
+    }
+
+    // Loop count n = play once, then repeat n more times
+    let plays = match decoder.repeat() {
+        gif::Repeat::Infinite => 0,
+        gif::Repeat::Finite(n) => n as u32 + 1,
+    };
+
+    Some((frames, plays))
+}
+
+// APNG: acTL chunk (plain PNG has no animation control)
+fn apng_info(data: &[u8]) -> Option<(u32, u32)> {
+    let reader = png::Decoder::new(Cursor::new(data)).read_info().ok()?;
+
+    Some(match reader.info().animation_control {
+        Some(control) => (control.num_frames, control.num_plays),
+        None => (1, 0),
+    })
+}
+
+// WebP: count ANMF chunks, loop count from ANIM chunk
+fn webp_info(data: &[u8]) -> Option<(u32, u32)> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut frames = 0;
+    let mut plays = 0;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let fourcc = &data[offset..offset + 4];
+        let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let payload = offset + 8;
+
+        match fourcc {
+            // Background color (4 bytes), then loop count (u16)
+            b"ANIM" if size >= 6 && payload + 6 <= data.len() => {
+                plays = u16::from_le_bytes([data[payload + 4], data[payload + 5]]) as u32;
+            }
+            b"ANMF" => frames += 1,
+            _ => {}
+        }
+
+        // Chunks are padded to an even size
+        offset = payload.saturating_add(size).saturating_add(size & 1);
+    }
+
+    Some((frames.max(1), plays))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::encode_animation;
+    use crate::converters::test_util::options;
+
+    const COLORS: [[u8; 3]; 3] = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+    const DELAYS: [u32; 3] = [100, 0, 250];
+
+    // Solid red, green and blue frames, the green one shown for zero time
+    fn animation(plays: u32) -> Animation {
+        let frames = COLORS
+            .iter()
+            .zip(DELAYS)
+            .map(|(&[r, g, b], delay_ms)| AnimationFrame {
+                image: image::RgbaImage::from_pixel(8, 8, image::Rgba([r, g, b, 255])),
+                delay_ms,
+            })
+            .collect();
+        Animation { frames, plays }
+    }
+
+    fn assert_round_trips(target_format: &str) {
+        for plays in [0, 1, 3] {
+            let data = encode_animation(&animation(plays), &options(target_format), None)
+                .unwrap()
+                .data;
+            assert_eq!(count_frames(&data), 3, "{} frame count", target_format);
+
+            let decoded = decode_animation(&data)
+                .unwrap()
+                .expect("decoded as an animation");
+            assert_eq!(decoded.plays, plays, "{} plays", target_format);
+            assert_eq!(decoded.dimensions(), (8, 8));
+
+            let delays: Vec<u32> = decoded.frames.iter().map(|frame| frame.delay_ms).collect();
+            assert_eq!(delays, DELAYS, "{} delays", target_format);
+
+            // Lossy WebP only comes close to the solid colors
+            for (frame, color) in decoded.frames.iter().zip(COLORS) {
+                let pixel = frame.image.get_pixel(4, 4).0;
+                let close = (0..3).all(|c| pixel[c].abs_diff(color[c]) <= 16);
+                assert!(
+                    close,
+                    "{} frame {:?}, expected {:?}",
+                    target_format, pixel, color
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gif_round_trip() {
+        assert_round_trips("gif");
+    }
+
+    #[test]
+    fn apng_round_trip() {
+        assert_round_trips("png");
+    }
+
+    #[test]
+    fn webp_round_trip() {
+        assert_round_trips("webp");
+    }
+
+    #[test]
+    fn still_images_are_not_animations() {
+        let still = crate::converters::encode_image(
+            &image::DynamicImage::new_rgb8(4, 4),
+            &options("png"),
+            None,
+        )
+        .unwrap()
+        .data;
+        assert_eq!(count_frames(&still), 1);
+        assert!(decode_animation(&still).unwrap().is_none());
+        assert_eq!(count_frames(b"not an image"), 1);
+    }
+}
@@ -1,14 +1,30 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 use tauri::Emitter;
 use tokio::sync::{mpsc, Semaphore};
 use uuid::Uuid;
 
-use crate::converters::{convert_to_avif, convert_to_jpeg, convert_to_png, convert_to_webp};
+use crate::animation::{count_frames, decode_animation};
+use crate::converters::{
+    combine_to_pdf, combine_to_tiff, encode_animation, encode_image, encode_source, favicon_set,
+    supports_animation, BELOW_MIN_QUALITY,
+};
+use crate::decode::{decode_image, decode_pages, decode_source};
 use crate::exif::{extract_exif_from_bytes, extract_exif_raw_bytes};
+use crate::metrics::compare_quality;
 use crate::models::{
-    ConversionProgress, ConversionResult, FileItem, FileItemResponse, FileTimestamps,
+    default_name_template, CombineOptions, ConversionOptions, ConversionPreview,
+    ConversionProgress, ConversionResult, FaviconOptions, FileItem, FileItemResponse,
+    FileTimestamps, OutputSpec, PdfOptions, RawOptions, SrcsetOptions, VideoInfo, VideoOptions,
+};
+use crate::pdf::{is_pdf, rasterize_pdf};
+use crate::srcset::{write_all, write_srcset, OUTPUT_EXISTS};
+use crate::state::{FileListState, VideoState};
+use crate::video::{
+    cancel_job, check_timestamps, extract_frame, interval_timestamps, locate_ffmpeg,
+    locate_ffprobe, output_extension, probe_video, start_job, transcode, TranscodeOutcome,
+    VideoInput,
 };
-use crate::state::FileListState;
 
 #[tauri::command]
 pub fn add_file_from_path(
@@ -35,6 +51,7 @@ pub fn add_file_from_path(
     // Extract EXIF
     let exif = extract_exif_from_bytes(&data);
     let exif_raw_bytes = extract_exif_raw_bytes(&data);
+    let frame_count = count_frames(&data);
 
     // Extract timestamps from original file
     let timestamps = std::fs::metadata(&path).ok().and_then(|metadata| {
@@ -68,6 +85,9 @@ pub fn add_file_from_path(
         timestamps,
         converted: false,
         converted_path: None,
+        output_paths: Vec::new(),
+        below_quality_threshold: false,
+        frame_count,
     };
 
     let response = file_item.to_response();
@@ -134,6 +154,7 @@ pub async fn add_file_from_url(
     // Extract EXIF
     let exif = extract_exif_from_bytes(&data);
     let exif_raw_bytes = extract_exif_raw_bytes(&data);
+    let frame_count = count_frames(&data);
 
     // Create file item (URL files don't have timestamps)
     let mut file_list = state.0.lock().unwrap();
@@ -150,6 +171,9 @@ pub async fn add_file_from_url(
         timestamps: None,
         converted: false,
         converted_path: None,
+        output_paths: Vec::new(),
+        below_quality_threshold: false,
+        frame_count,
     };
 
     let response = file_item.to_response();
@@ -158,6 +182,76 @@ pub async fn add_file_from_url(
     Ok(response)
 }
 
+// Rasterize a PDF and add every page as its own PNG file item ({stem}-p{page}.png),
+// so outputs are named {stem}-p{page}.{ext} by the normal conversion
+#[tauri::command]
+pub async fn add_pdf_pages(
+    path: String,
+    options: Option<PdfOptions>,
+    state: tauri::State<'_, FileListState>,
+) -> Result<Vec<FileItemResponse>, String> {
+    let options = options.unwrap_or_default();
+    let data = std::fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+    if !is_pdf(&data) {
+        return Err(format!("Not a PDF file: {}", path));
+    }
+
+    // Check for duplicate path - only block if unconverted pages exist
+    if state
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|f| f.source_path.as_ref() == Some(&path) && !f.converted)
+    {
+        return Err(format!("File already added: {}", path));
+    }
+
+    let pages = tokio::task::spawn_blocking(move || rasterize_pdf(&data, &options))
+        .await
+        .map_err(|e| format!("PDF task failed: {}", e))??;
+
+    let stem = std::path::Path::new(&path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("document")
+        .to_string();
+
+    // Pages keep the PDF's timestamps
+    let timestamps = std::fs::metadata(&path).ok().and_then(|metadata| {
+        let accessed = metadata.accessed().ok()?;
+        let modified = metadata.modified().ok()?;
+        Some(FileTimestamps { accessed, modified })
+    });
+
+    let mut file_list = state.0.lock().unwrap();
+    let mut responses = Vec::with_capacity(pages.len());
+    for (page, data) in pages {
+        let file_item = FileItem {
+            id: Uuid::new_v4().to_string(),
+            name: format!("{}-p{}.png", stem, page),
+            size: data.len() as u64,
+            mime_type: "image/png".to_string(),
+            data,
+            source_path: Some(path.clone()),
+            source_url: None,
+            exif: None,
+            exif_raw_bytes: None,
+            timestamps: timestamps.clone(),
+            converted: false,
+            converted_path: None,
+            output_paths: Vec::new(),
+            below_quality_threshold: false,
+            frame_count: 1,
+        };
+
+        responses.push(file_item.to_response());
+        file_list.push(file_item);
+    }
+
+    Ok(responses)
+}
+
 #[tauri::command]
 pub fn remove_file(id: String, state: tauri::State<FileListState>) -> Result<(), String> {
     let mut file_list = state.0.lock().unwrap();
@@ -185,6 +279,23 @@ pub fn remove_converted_files(state: tauri::State<FileListState>) -> Result<(),
     Ok(())
 }
 
+#[tauri::command]
+pub fn requeue_low_quality_files(
+    state: tauri::State<FileListState>,
+) -> Result<Vec<FileItemResponse>, String> {
+    let mut file_list = state.0.lock().unwrap();
+    let mut requeued = Vec::new();
+
+    // Outputs stay on disk, the next conversion rewrites the file's own outputs
+    for file in file_list.iter_mut().filter(|f| f.below_quality_threshold) {
+        file.converted = false;
+        file.below_quality_threshold = false;
+        requeued.push(file.to_response());
+    }
+
+    Ok(requeued)
+}
+
 #[tauri::command]
 pub fn get_file_list(state: tauri::State<FileListState>) -> Vec<FileItemResponse> {
     let file_list = state.0.lock().unwrap();
@@ -214,18 +325,103 @@ pub fn get_cpu_count() -> usize {
     num_cpus::get()
 }
 
+#[tauri::command]
+pub async fn preview_conversion(
+    id: String,
+    options: ConversionOptions,
+    max_preview_size: Option<u32>,
+    state: tauri::State<'_, FileListState>,
+) -> Result<ConversionPreview, String> {
+    // Clone file data to release Mutex lock before encoding
+    let (data, original_size, exif_raw_bytes) = {
+        let file_list = state.0.lock().unwrap();
+        let file = file_list
+            .iter()
+            .find(|f| f.id == id)
+            .ok_or_else(|| "File not found".to_string())?;
+        (file.data.clone(), file.size, file.exif_raw_bytes.clone())
+    };
+
+    // Encode in blocking thread pool (same path as convert_images, without writing to disk)
+    tokio::task::spawn_blocking(move || {
+        let img = decode_source(&data, &options.raw)?;
+
+        let exif_to_use = if options.preserve_exif {
+            exif_raw_bytes.as_deref()
+        } else {
+            None
+        };
+        let animation = if keeps_animation(&options) {
+            decode_animation(&data)?
+        } else {
+            None
+        };
+        let encoded = match &animation {
+            Some(animation) => encode_animation(animation, &options, exif_to_use)?,
+            None => encode_source(&data, &img, &options, exif_to_use)?,
+        };
+        let converted_size = encoded.data.len() as u64;
+        let chosen_quality = encoded.quality;
+        let dssim = encoded.dssim;
+
+        // Downscaled view for display, encoded with the same options to show artifacts
+        // Animations are returned at full size with every frame
+        let (preview_data, width, height) = match max_preview_size {
+            Some(max)
+                if max > 0 && animation.is_none() && (img.width() > max || img.height() > max) =>
+            {
+                // Fixed at the quality searched on the full image: a byte budget or DSSIM
+                // target applied to the thumbnail would pick a different quality
+                let thumbnail_options = ConversionOptions {
+                    quality: chosen_quality.unwrap_or(options.quality),
+                    max_file_size: None,
+                    max_dssim: None,
+                    ..options.clone()
+                };
+                let thumbnail = img.thumbnail(max, max);
+                let data = encode_image(&thumbnail, &thumbnail_options, None)?.data;
+                (data, thumbnail.width(), thumbnail.height())
+            }
+            _ => {
+                let (width, height) = encoded.resized_to.unwrap_or((img.width(), img.height()));
+                (encoded.data, width, height)
+            }
+        };
+
+        let compression_ratio = if original_size > 0 {
+            converted_size as f64 / original_size as f64
+        } else {
+            0.0
+        };
+
+        Ok(ConversionPreview {
+            data: preview_data,
+            width,
+            height,
+            original_size,
+            converted_size,
+            compression_ratio,
+            chosen_quality,
+            dssim,
+        })
+    })
+    .await
+    .map_err(|e| format!("Preview task failed: {}", e))?
+}
+
 #[tauri::command]
 pub async fn convert_images(
-    target_format: String,
-    quality: u8,
-    avif_speed: u8,
-    preserve_exif: bool,
+    options: ConversionOptions, // Encoding options shared by every file in this batch
     preserve_timestamps: bool,
     output_dir: String,
     max_concurrent: usize,
     create_subfolder: bool,
     subfolder_name: String,
     url_files_fallback_dir: String,
+    compute_quality_metrics: Option<bool>,
+    min_ssim: Option<f64>,
+    srcset_options: Option<SrcsetOptions>, // Responsive image set mode instead of one output
+    outputs: Option<Vec<OutputSpec>>,      // Several outputs per file instead of the options above
     window: tauri::Window,
     state: tauri::State<'_, FileListState>,
 ) -> Result<Vec<ConversionResult>, String> {
@@ -238,6 +434,7 @@ pub async fn convert_images(
         Option<Vec<u8>>,
         Option<FileTimestamps>,
         Option<String>,
+        Vec<String>,
     )> = {
         let file_list = state.0.lock().unwrap();
 
@@ -252,7 +449,8 @@ pub async fn convert_images(
                     f.data.clone(),
                     f.exif_raw_bytes.clone(), // For img-parts (JPEG, PNG, WebP)
                     f.timestamps.clone(),
-                    f.source_path.clone(), // For source directory mode
+                    f.source_path.clone(),  // For source directory mode
+                    f.output_paths.clone(), // Outputs of a requeued file may be rewritten
                 )
             })
             .collect()
@@ -262,8 +460,24 @@ pub async fn convert_images(
         return Err("No files to convert (all files already converted)".to_string());
     }
 
-    // Check if using source directory mode
-    let use_source_dir = output_dir == "USE_SOURCE_DIR";
+    // Every output written per file, the batch options alone when no specs are given
+    let outputs = match outputs {
+        Some(outputs) if !outputs.is_empty() => {
+            if srcset_options.is_some() {
+                return Err("Srcset mode and output specs cannot be combined".to_string());
+            }
+            // Same stem for every spec, pages are checked per file once decoded
+            check_output_names(&outputs, &["{stem}"])?;
+            outputs
+        }
+        _ => vec![OutputSpec {
+            options: options.clone(),
+            name: default_name_template(),
+        }],
+    };
+
+    // Quality report options (PSNR / SSIM per converted file), a threshold needs the metrics
+    let compute_quality_metrics = compute_quality_metrics.unwrap_or(false) || min_ssim.is_some();
 
     // Determine concurrent processing count
     // 0 = auto (CPU cores), 1+ = manual value
@@ -281,17 +495,20 @@ pub async fn convert_images(
     let state_arc = state.0.clone();
 
     // Process files concurrently with order preservation
-    for (index, (id, name, original_size, data, exif_raw_bytes, timestamps, source_path)) in
-        files_to_convert.into_iter().enumerate()
+    for (
+        index,
+        (id, name, original_size, data, exif_raw_bytes, timestamps, source_path, previous_outputs),
+    ) in files_to_convert.into_iter().enumerate()
     {
         let window = window.clone();
-        let target_format = target_format.clone();
+        let options = options.clone();
+        let outputs = outputs.clone();
         let output_dir = output_dir.clone();
         let subfolder_name = subfolder_name.clone();
         let url_fallback = url_files_fallback_dir.clone();
+        let srcset = srcset_options.clone();
         let semaphore = Arc::clone(&semaphore);
         let result_tx = result_tx.clone();
-        let avif_speed_clone = avif_speed;
         let state_clone = state_arc.clone();
 
         tokio::spawn(async move {
@@ -309,396 +526,48 @@ pub async fn convert_images(
                         status: "converting".to_string(),
                         error_message: None,
                         saved_path: None,
+                        quality_report: None,
+                        progress: None,
                     },
                 );
 
-                // Load image from bytes
-                let img = match image::load_from_memory(&data) {
-                    Ok(img) => img,
-                    Err(e) => {
-                        // If standard decoding fails, try AVIF decoding
-                        // AVIF decode → RGBA/RGB pixels → DynamicImage → target format
-                        match avif_decode::Decoder::from_avif(&data) {
-                            Ok(decoder) => {
-                                match decoder.to_image() {
-                                    Ok(avif_image) => {
-                                        // Convert avif_decode::Image to image::DynamicImage
-                                        match avif_image {
-                                            avif_decode::Image::Rgba8(img) => {
-                                                // RGBA8 → Vec<u8>
-                                                let pixels: Vec<u8> = img.buf()
-                                                    .iter()
-                                                    .flat_map(|px| [px.r, px.g, px.b, px.a])
-                                                    .collect();
-                                                match image::RgbaImage::from_raw(
-                                                    img.width() as u32,
-                                                    img.height() as u32,
-                                                    pixels,
-                                                ) {
-                                                    Some(rgba_img) => image::DynamicImage::ImageRgba8(rgba_img),
-                                                    None => {
-                                                        let error_msg = "Failed to create RGBA image from AVIF".to_string();
-                                                        let _ = window.emit(
-                                                            "conversion-progress",
-                                                            ConversionProgress {
-                                                                file_id: id.clone(),
-                                                                file_name: name.clone(),
-                                                                status: "error".to_string(),
-                                                                error_message: Some(error_msg.clone()),
-                                                                saved_path: None,
-                                                            },
-                                                        );
-                                                        eprintln!("{}", error_msg);
-                                                        return None;
-                                                    }
-                                                }
-                                            }
-                                            avif_decode::Image::Rgb8(img) => {
-                                                // RGB8 → Vec<u8>
-                                                let pixels: Vec<u8> = img.buf()
-                                                    .iter()
-                                                    .flat_map(|px| [px.r, px.g, px.b])
-                                                    .collect();
-                                                match image::RgbImage::from_raw(
-                                                    img.width() as u32,
-                                                    img.height() as u32,
-                                                    pixels,
-                                                ) {
-                                                    Some(rgb_img) => image::DynamicImage::ImageRgb8(rgb_img),
-                                                    None => {
-                                                        let error_msg = "Failed to create RGB image from AVIF".to_string();
-                                                        let _ = window.emit(
-                                                            "conversion-progress",
-                                                            ConversionProgress {
-                                                                file_id: id.clone(),
-                                                                file_name: name.clone(),
-                                                                status: "error".to_string(),
-                                                                error_message: Some(error_msg.clone()),
-                                                                saved_path: None,
-                                                            },
-                                                        );
-                                                        eprintln!("{}", error_msg);
-                                                        return None;
-                                                    }
-                                                }
-                                            }
-                                            avif_decode::Image::Rgba16(img) => {
-                                                // RGBA16 → RGBA8 conversion (simple downscaling)
-                                                let pixels: Vec<u8> = img.buf()
-                                                    .iter()
-                                                    .flat_map(|px| [
-                                                        (px.r >> 8) as u8,
-                                                        (px.g >> 8) as u8,
-                                                        (px.b >> 8) as u8,
-                                                        (px.a >> 8) as u8,
-                                                    ])
-                                                    .collect();
-                                                match image::RgbaImage::from_raw(
-                                                    img.width() as u32,
-                                                    img.height() as u32,
-                                                    pixels,
-                                                ) {
-                                                    Some(rgba_img) => image::DynamicImage::ImageRgba8(rgba_img),
-                                                    None => {
-                                                        let error_msg = "Failed to create RGBA image from AVIF (16-bit)".to_string();
-                                                        let _ = window.emit(
-                                                            "conversion-progress",
-                                                            ConversionProgress {
-                                                                file_id: id.clone(),
-                                                                file_name: name.clone(),
-                                                                status: "error".to_string(),
-                                                                error_message: Some(error_msg.clone()),
-                                                                saved_path: None,
-                                                            },
-                                                        );
-                                                        eprintln!("{}", error_msg);
-                                                        return None;
-                                                    }
-                                                }
-                                            }
-                                            avif_decode::Image::Rgb16(img) => {
-                                                // RGB16 → RGB8 conversion
-                                                let pixels: Vec<u8> = img.buf()
-                                                    .iter()
-                                                    .flat_map(|px| [
-                                                        (px.r >> 8) as u8,
-                                                        (px.g >> 8) as u8,
-                                                        (px.b >> 8) as u8,
-                                                    ])
-                                                    .collect();
-                                                match image::RgbImage::from_raw(
-                                                    img.width() as u32,
-                                                    img.height() as u32,
-                                                    pixels,
-                                                ) {
-                                                    Some(rgb_img) => image::DynamicImage::ImageRgb8(rgb_img),
-                                                    None => {
-                                                        let error_msg = "Failed to create RGB image from AVIF (16-bit)".to_string();
-                                                        let _ = window.emit(
-                                                            "conversion-progress",
-                                                            ConversionProgress {
-                                                                file_id: id.clone(),
-                                                                file_name: name.clone(),
-                                                                status: "error".to_string(),
-                                                                error_message: Some(error_msg.clone()),
-                                                                saved_path: None,
-                                                            },
-                                                        );
-                                                        eprintln!("{}", error_msg);
-                                                        return None;
-                                                    }
-                                                }
-                                            }
-                                            avif_decode::Image::Gray8(img) => {
-                                                // Gray8 → Luma8
-                                                let pixels: Vec<u8> = img.buf()
-                                                    .iter()
-                                                    .map(|px| px.value())
-                                                    .collect();
-                                                match image::GrayImage::from_raw(
-                                                    img.width() as u32,
-                                                    img.height() as u32,
-                                                    pixels,
-                                                ) {
-                                                    Some(gray_img) => image::DynamicImage::ImageLuma8(gray_img),
-                                                    None => {
-                                                        let error_msg = "Failed to create Gray image from AVIF".to_string();
-                                                        let _ = window.emit(
-                                                            "conversion-progress",
-                                                            ConversionProgress {
-                                                                file_id: id.clone(),
-                                                                file_name: name.clone(),
-                                                                status: "error".to_string(),
-                                                                error_message: Some(error_msg.clone()),
-                                                                saved_path: None,
-                                                            },
-                                                        );
-                                                        eprintln!("{}", error_msg);
-                                                        return None;
-                                                    }
-                                                }
-                                            }
-                                            avif_decode::Image::Gray16(img) => {
-                                                // Gray16 → Luma8 conversion
-                                                let pixels: Vec<u8> = img.buf()
-                                                    .iter()
-                                                    .map(|px| (px.value() >> 8) as u8)
-                                                    .collect();
-                                                match image::GrayImage::from_raw(
-                                                    img.width() as u32,
-                                                    img.height() as u32,
-                                                    pixels,
-                                                ) {
-                                                    Some(gray_img) => image::DynamicImage::ImageLuma8(gray_img),
-                                                    None => {
-                                                        let error_msg = "Failed to create Gray image from AVIF (16-bit)".to_string();
-                                                        let _ = window.emit(
-                                                            "conversion-progress",
-                                                            ConversionProgress {
-                                                                file_id: id.clone(),
-                                                                file_name: name.clone(),
-                                                                status: "error".to_string(),
-                                                                error_message: Some(error_msg.clone()),
-                                                                saved_path: None,
-                                                            },
-                                                        );
-                                                        eprintln!("{}", error_msg);
-                                                        return None;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    Err(to_image_err) => {
-                                        let error_msg = format!("Failed to convert AVIF to image: {}", to_image_err);
-                                        let _ = window.emit(
-                                            "conversion-progress",
-                                            ConversionProgress {
-                                                file_id: id.clone(),
-                                                file_name: name.clone(),
-                                                status: "error".to_string(),
-                                                error_message: Some(error_msg.clone()),
-                                                saved_path: None,
-                                            },
-                                        );
-                                        eprintln!("{}", error_msg);
-                                        return None;
-                                    }
-                                }
-                            }
-                            Err(avif_err) => {
-                                // Both standard and AVIF decoding failed
-                                let error_msg = format!("Failed to decode image: {} (AVIF decode also failed: {})", e, avif_err);
-                                let _ = window.emit(
-                                    "conversion-progress",
-                                    ConversionProgress {
-                                        file_id: id.clone(),
-                                        file_name: name.clone(),
-                                        status: "error".to_string(),
-                                        error_message: Some(error_msg.clone()),
-                                        saved_path: None,
-                                    },
-                                );
-                                eprintln!("{}", error_msg);
-                                return None;
-                            }
-                        }
+                // Load image from bytes (every page of a multi-page TIFF)
+                let pages = match decode_pages(&data, &options.raw) {
+                    Ok(pages) => pages,
+                    Err(error_msg) => {
+                        let _ = window.emit(
+                            "conversion-progress",
+                            ConversionProgress {
+                                file_id: id.clone(),
+                                file_name: name.clone(),
+                                status: "error".to_string(),
+                                error_message: Some(error_msg.clone()),
+                                saved_path: None,
+                                quality_report: None,
+                                progress: None,
+                            },
+                        );
+                        eprintln!("{}", error_msg);
+                        return Vec::new();
                     }
                 };
 
-                // Generate output filename
-                let output_name = format!(
-                    "{}.{}",
-                    std::path::Path::new(&name)
-                        .file_stem()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or("image"),
-                    target_format
-                );
+                // Output names are built from the source file stem
+                let stem = std::path::Path::new(&name)
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("image");
 
                 // Determine output directory
-                let output_path = if use_source_dir {
-                    // Use source directory mode
-                    match &source_path {
-                        Some(path) => {
-                            // Extract directory from source path
-                            let source_dir = std::path::Path::new(path)
-                                .parent()
-                                .ok_or_else(|| "Failed to get source directory".to_string());
-
-                            match source_dir {
-                                Ok(dir) => {
-                                    // Determine final directory based on subfolder settings
-                                    let final_dir = if create_subfolder && !subfolder_name.is_empty() {
-                                        // Create subfolder if it doesn't exist
-                                        let subfolder_path = dir.join(&subfolder_name);
-                                        if !subfolder_path.exists() {
-                                            if let Err(e) = std::fs::create_dir_all(&subfolder_path) {
-                                                let error_msg = format!("Failed to create subfolder: {}", e);
-                                                let _ = window.emit(
-                                                    "conversion-progress",
-                                                    ConversionProgress {
-                                                        file_id: id.clone(),
-                                                        file_name: name.clone(),
-                                                        status: "error".to_string(),
-                                                        error_message: Some(error_msg.clone()),
-                                                        saved_path: None,
-                                                    },
-                                                );
-                                                eprintln!("{}", error_msg);
-                                                return None;
-                                            }
-                                        }
-                                        subfolder_path
-                                    } else {
-                                        // Save directly in source directory
-                                        dir.to_path_buf()
-                                    };
-
-                                    final_dir.join(&output_name)
-                                }
-                                Err(e) => {
-                                    let error_msg =
-                                        format!("Failed to get source directory: {}", e);
-                                    let _ = window.emit(
-                                        "conversion-progress",
-                                        ConversionProgress {
-                                            file_id: id.clone(),
-                                            file_name: name.clone(),
-                                            status: "error".to_string(),
-                                            error_message: Some(error_msg.clone()),
-                                            saved_path: None,
-                                        },
-                                    );
-                                    eprintln!("{}", error_msg);
-                                    return None;
-                                }
-                            }
-                        }
-                        None => {
-                            // URL 파일의 경우 fallback 디렉토리 사용
-                            let fallback_dir = if url_fallback.is_empty() {
-                                // 빈 문자열이면 Downloads 폴더 사용
-                                match dirs::download_dir() {
-                                    Some(dir) => dir,
-                                    None => {
-                                        let error_msg = "Cannot determine Downloads folder".to_string();
-                                        let _ = window.emit(
-                                            "conversion-progress",
-                                            ConversionProgress {
-                                                file_id: id.clone(),
-                                                file_name: name.clone(),
-                                                status: "error".to_string(),
-                                                error_message: Some(error_msg.clone()),
-                                                saved_path: None,
-                                            },
-                                        );
-                                        eprintln!("{}", error_msg);
-                                        return None;
-                                    }
-                                }
-                            } else {
-                                // 사용자가 지정한 폴더 사용
-                                std::path::PathBuf::from(&url_fallback)
-                            };
-
-                            // Subfolder 처리
-                            let final_dir = if create_subfolder && !subfolder_name.is_empty() {
-                                let subfolder_path = fallback_dir.join(&subfolder_name);
-                                if !subfolder_path.exists() {
-                                    if let Err(e) = std::fs::create_dir_all(&subfolder_path) {
-                                        let error_msg = format!("Failed to create subfolder: {}", e);
-                                        let _ = window.emit(
-                                            "conversion-progress",
-                                            ConversionProgress {
-                                                file_id: id.clone(),
-                                                file_name: name.clone(),
-                                                status: "error".to_string(),
-                                                error_message: Some(error_msg.clone()),
-                                                saved_path: None,
-                                            },
-                                        );
-                                        eprintln!("{}", error_msg);
-                                        return None;
-                                    }
-                                }
-                                subfolder_path
-                            } else {
-                                fallback_dir
-                            };
-
-                            final_dir.join(&output_name)
-                        }
-                    }
-                } else {
-                    std::path::Path::new(&output_dir).join(&output_name)
-                };
-
-                // Check if file already exists at output path
-                if output_path.exists() {
-                    let _ = window.emit(
-                        "conversion-progress",
-                        ConversionProgress {
-                            file_id: id.clone(),
-                            file_name: name.clone(),
-                            status: "skipped".to_string(),
-                            error_message: Some("File already exists".to_string()),
-                            saved_path: None,
-                        },
-                    );
-                    return None;
-                }
-
-                // Convert based on target format
-                let exif_to_use = if preserve_exif {
-                    exif_raw_bytes.as_deref()
-                } else {
-                    None
-                };
-                let converted_data = match target_format.as_str() {
-                    "error" => {
-                        // Dev mode: intentional error for testing
-                        let error_msg = "Intentional error for testing (dev mode)".to_string();
+                let target_dir = match conversion_output_dir(
+                    &output_dir,
+                    source_path.as_deref(),
+                    create_subfolder,
+                    &subfolder_name,
+                    &url_fallback,
+                ) {
+                    Ok(dir) => dir,
+                    Err(error_msg) => {
                         let _ = window.emit(
                             "conversion-progress",
                             ConversionProgress {
@@ -707,151 +576,127 @@ pub async fn convert_images(
                                 status: "error".to_string(),
                                 error_message: Some(error_msg.clone()),
                                 saved_path: None,
+                                quality_report: None,
+                                progress: None,
                             },
                         );
                         eprintln!("{}", error_msg);
-                        return None;
+                        return Vec::new();
                     }
-                    "webp" => match convert_to_webp(&img, quality, exif_to_use) {
-                        Ok(data) => data,
-                        Err(e) => {
-                            let _ = window.emit(
-                                "conversion-progress",
-                                ConversionProgress {
-                                    file_id: id.clone(),
-                                    file_name: name.clone(),
-                                    status: "error".to_string(),
-                                    error_message: Some(e.clone()),
-                                    saved_path: None,
-                                },
-                            );
-                            eprintln!("{}", e);
-                            return None;
-                        }
-                    },
-                    "jpeg" | "jpg" => match convert_to_jpeg(&img, quality, exif_to_use) {
-                        Ok(data) => data,
-                        Err(e) => {
-                            let _ = window.emit(
-                                "conversion-progress",
-                                ConversionProgress {
-                                    file_id: id.clone(),
-                                    file_name: name.clone(),
-                                    status: "error".to_string(),
-                                    error_message: Some(e.clone()),
-                                    saved_path: None,
-                                },
-                            );
-                            eprintln!("{}", e);
-                            return None;
-                        }
-                    },
-                    "png" => match convert_to_png(&img, quality, exif_to_use) {
-                        Ok(data) => data,
-                        Err(e) => {
-                            let _ = window.emit(
-                                "conversion-progress",
-                                ConversionProgress {
-                                    file_id: id.clone(),
-                                    file_name: name.clone(),
-                                    status: "error".to_string(),
-                                    error_message: Some(e.clone()),
-                                    saved_path: None,
-                                },
-                            );
-                            eprintln!("{}", e);
-                            return None;
-                        }
-                    },
-                    "avif" => match convert_to_avif(&img, quality, avif_speed_clone, exif_to_use) {
-                        Ok(data) => data,
+                };
+
+                // Srcset mode: every width and format goes where the single output would
+                // (first page only for multi-page sources)
+                if let Some(srcset) = &srcset {
+                    let exif_to_use = if options.preserve_exif {
+                        exif_raw_bytes.as_deref()
+                    } else {
+                        None
+                    };
+                    let set = match write_srcset(
+                        &pages[0],
+                        stem,
+                        &target_dir,
+                        &options,
+                        srcset,
+                        exif_to_use,
+                    ) {
+                        Ok(set) => set,
                         Err(e) => {
+                            let status = if e.starts_with(OUTPUT_EXISTS) {
+                                "skipped"
+                            } else {
+                                "error"
+                            };
                             let _ = window.emit(
                                 "conversion-progress",
                                 ConversionProgress {
                                     file_id: id.clone(),
                                     file_name: name.clone(),
-                                    status: "error".to_string(),
+                                    status: status.to_string(),
                                     error_message: Some(e.clone()),
                                     saved_path: None,
+                                    quality_report: None,
+                                    progress: None,
                                 },
                             );
                             eprintln!("{}", e);
-                            return None;
+                            return Vec::new();
                         }
-                    },
-                    "tiff" => {
-                        // TIFF: Basic encoding without EXIF preservation
-                        let mut buffer = Vec::new();
-                        match img.write_to(
-                            &mut std::io::Cursor::new(&mut buffer),
-                            image::ImageFormat::Tiff,
-                        ) {
-                            Ok(_) => buffer,
-                            Err(e) => {
-                                let error_msg = format!("TIFF encoding failed: {}", e);
-                                let _ = window.emit(
-                                    "conversion-progress",
-                                    ConversionProgress {
-                                        file_id: id.clone(),
-                                        file_name: name.clone(),
-                                        status: "error".to_string(),
-                                        error_message: Some(error_msg.clone()),
-                                        saved_path: None,
-                                    },
-                                );
-                                eprintln!("{}", error_msg);
-                                return None;
-                            }
-                        }
-                    }
-                    _ => {
-                        // Use image crate's default encoder for other formats
-                        let mut buffer = Vec::new();
-                        let format = match image::ImageFormat::from_extension(&target_format) {
-                            Some(fmt) => fmt,
-                            None => {
-                                let error_msg = format!("Unsupported format: {}", target_format);
-                                let _ = window.emit(
-                                    "conversion-progress",
-                                    ConversionProgress {
-                                        file_id: id.clone(),
-                                        file_name: name.clone(),
-                                        status: "error".to_string(),
-                                        error_message: Some(error_msg.clone()),
-                                        saved_path: None,
-                                    },
-                                );
-                                eprintln!("{}", error_msg);
-                                return None;
-                            }
-                        };
+                    };
 
-                        match img.write_to(&mut std::io::Cursor::new(&mut buffer), format) {
-                            Ok(_) => buffer,
-                            Err(e) => {
-                                let error_msg =
-                                    format!("Failed to encode {}: {}", target_format, e);
-                                let _ = window.emit(
-                                    "conversion-progress",
-                                    ConversionProgress {
-                                        file_id: id.clone(),
-                                        file_name: name.clone(),
-                                        status: "error".to_string(),
-                                        error_message: Some(error_msg.clone()),
-                                        saved_path: None,
-                                    },
-                                );
-                                eprintln!("{}", error_msg);
-                                return None;
-                            }
+                    // The <img> fallback (last format, largest width, written last)
+                    // stands in for the set in the file list
+                    let Some(fallback) = set.images.last() else {
+                        return Vec::new();
+                    };
+                    let saved_path = fallback.saved_path.clone();
+                    let converted_name = fallback.file_name.clone();
+
+                    let _ = window.emit(
+                        "conversion-progress",
+                        ConversionProgress {
+                            file_id: id.clone(),
+                            file_name: name.clone(),
+                            status: "completed".to_string(),
+                            error_message: None,
+                            saved_path: Some(saved_path.clone()),
+                            quality_report: None,
+                            progress: None,
+                        },
+                    );
+
+                    {
+                        let mut file_list = state_clone.lock().unwrap();
+                        if let Some(file) = file_list.iter_mut().find(|f| f.id == id) {
+                            file.converted = true;
+                            file.converted_path = Some(saved_path.clone());
+                            file.output_paths = set
+                                .images
+                                .iter()
+                                .map(|image| image.saved_path.clone())
+                                .chain(std::iter::once(set.manifest_path.clone()))
+                                .collect();
                         }
                     }
+
+                    return vec![ConversionResult {
+                        original_name: name,
+                        converted_name,
+                        original_size,
+                        // Total of every image in the set
+                        converted_size: set.images.iter().map(|image| image.size).sum(),
+                        saved_path,
+                        chosen_quality: None,
+                        resized_to: None,
+                        dssim: None,
+                        quality_report: None,
+                        srcset: Some(set),
+                    }];
+                }
+
+                // Animated sources keep every frame when the target supports it,
+                // frames are decoded once for all outputs that can store them
+                let animation = if outputs.iter().any(|spec| keeps_animation(&spec.options)) {
+                    decode_animation(&data)
+                } else {
+                    Ok(None)
+                };
+
+                // Pages of a multi-page source are written as {stem}-p{page}
+                let page_stems: Vec<(String, &image::DynamicImage)> = if pages.len() > 1 {
+                    pages
+                        .iter()
+                        .enumerate()
+                        .map(|(index, img)| (format!("{}-p{}", stem, index + 1), img))
+                        .collect()
+                } else {
+                    pages.iter().map(|img| (stem.to_string(), img)).collect()
                 };
 
-                // Write to file
-                if let Err(e) = std::fs::write(&output_path, &converted_data) {
-                    let error_msg = format!("Failed to write file: {}", e);
+                // Pages and output specs must not overwrite each other
+                let stems: Vec<&str> = page_stems.iter().map(|(stem, _)| stem.as_str()).collect();
+                if let Err(error_msg) = check_output_names(&outputs, &stems) {
                     let _ = window.emit(
                         "conversion-progress",
                         ConversionProgress {
@@ -860,32 +705,154 @@ pub async fn convert_images(
                             status: "error".to_string(),
                             error_message: Some(error_msg.clone()),
                             saved_path: None,
+                            quality_report: None,
+                            progress: None,
                         },
                     );
                     eprintln!("{}", error_msg);
-                    return None;
+                    return Vec::new();
                 }
 
-                // Preserve timestamps if requested and available
-                if preserve_timestamps {
-                    if let Some(ref ts) = timestamps {
-                        let _ = filetime::set_file_times(
-                            &output_path,
-                            filetime::FileTime::from_system_time(ts.accessed),
-                            filetime::FileTime::from_system_time(ts.modified),
-                        );
+                let mut results = Vec::new();
+                let mut failures: Vec<(&str, String)> = Vec::new();
+                for (spec, (page_stem, img)) in outputs
+                    .iter()
+                    .flat_map(|spec| page_stems.iter().map(move |page| (spec, page)))
+                {
+                    let options = &spec.options;
+                    let output_name = output_name(spec, page_stem);
+                    let output_path = target_dir.join(&output_name);
+                    let exif_to_use = if options.preserve_exif {
+                        exif_raw_bytes.as_deref()
+                    } else {
+                        None
+                    };
+
+                    // Check if file already exists at output path
+                    // (a requeued file replaces what it wrote in the previous run)
+                    let saved_path = output_path.to_string_lossy().to_string();
+                    if output_path.exists() && !previous_outputs.contains(&saved_path) {
+                        failures.push(("skipped", format!("File already exists: {}", output_name)));
+                        continue;
+                    }
+
+                    let encoded = match &animation {
+                        Err(e) if keeps_animation(options) => Err(e.clone()),
+                        Ok(Some(animation)) if keeps_animation(options) => {
+                            encode_animation(animation, options, exif_to_use)
+                        }
+                        _ => encode_source(&data, img, options, exif_to_use),
+                    };
+                    let encoded = match encoded {
+                        Ok(encoded) => encoded,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            // Quantized PNG that cannot reach the minimum quality is not written
+                            let status = if e.starts_with(BELOW_MIN_QUALITY) {
+                                "skipped"
+                            } else {
+                                "error"
+                            };
+                            failures.push((status, e));
+                            continue;
+                        }
+                    };
+
+                    // Write to file
+                    if let Err(e) = std::fs::write(&output_path, &encoded.data) {
+                        let error_msg = format!("Failed to write file: {}", e);
+                        eprintln!("{}", error_msg);
+                        failures.push(("error", error_msg));
+                        continue;
                     }
+
+                    // Preserve timestamps if requested and available
+                    if preserve_timestamps {
+                        if let Some(ref ts) = timestamps {
+                            let _ = filetime::set_file_times(
+                                &output_path,
+                                filetime::FileTime::from_system_time(ts.accessed),
+                                filetime::FileTime::from_system_time(ts.modified),
+                            );
+                        }
+                    }
+
+                    // Compare decoded output against source (PSNR / SSIM)
+                    let quality_report = if compute_quality_metrics {
+                        match compare_quality(img, &encoded, options, min_ssim) {
+                            Ok(report) => Some(report),
+                            Err(e) => {
+                                // Metrics are informational, conversion itself succeeded
+                                let error_msg = format!(
+                                    "Failed to compute quality metrics for {}: {}",
+                                    output_name, e
+                                );
+                                eprintln!("{}", error_msg);
+                                failures.push(("error", error_msg));
+                                None
+                            }
+                        }
+                    } else {
+                        None
+                    };
+
+                    results.push(ConversionResult {
+                        original_name: name.clone(),
+                        converted_name: output_name,
+                        original_size,
+                        converted_size: encoded.data.len() as u64,
+                        saved_path,
+                        chosen_quality: encoded.quality,
+                        resized_to: encoded.resized_to,
+                        dssim: encoded.dssim,
+                        quality_report,
+                        srcset: None,
+                    });
                 }
 
-                // Emit conversion complete event
+                // Nothing written: report the last failure for the file
+                let Some(last) = results.last() else {
+                    if let Some((status, message)) = failures.pop() {
+                        let _ = window.emit(
+                            "conversion-progress",
+                            ConversionProgress {
+                                file_id: id.clone(),
+                                file_name: name.clone(),
+                                status: status.to_string(),
+                                error_message: Some(message),
+                                saved_path: None,
+                                quality_report: None,
+                                progress: None,
+                            },
+                        );
+                    }
+                    return Vec::new();
+                };
+                let saved_path = last.saved_path.clone();
+
+                // Worst output decides the file's quality report
+                let quality_report = results
+                    .iter()
+                    .filter_map(|result| result.quality_report.clone())
+                    .min_by(|a, b| a.ssim.total_cmp(&b.ssim));
+
+                // Emit conversion complete event (failed outputs listed in error_message)
                 let _ = window.emit(
                     "conversion-progress",
                     ConversionProgress {
                         file_id: id.clone(),
                         file_name: name.clone(),
                         status: "completed".to_string(),
-                        error_message: None,
-                        saved_path: Some(output_path.to_string_lossy().to_string()),
+                        error_message: (!failures.is_empty()).then(|| {
+                            failures
+                                .iter()
+                                .map(|(_, message)| message.as_str())
+                                .collect::<Vec<_>>()
+                                .join("; ")
+                        }),
+                        saved_path: Some(saved_path.clone()),
+                        quality_report: quality_report.clone(),
+                        progress: None,
                     },
                 );
 
@@ -894,22 +861,22 @@ pub async fn convert_images(
                     let mut file_list = state_clone.lock().unwrap();
                     if let Some(file) = file_list.iter_mut().find(|f| f.id == id) {
                         file.converted = true;
-                        file.converted_path = Some(output_path.to_string_lossy().to_string());
+                        file.converted_path = Some(saved_path);
+                        file.output_paths = results
+                            .iter()
+                            .map(|result| result.saved_path.clone())
+                            .collect();
+                        file.below_quality_threshold = quality_report
+                            .as_ref()
+                            .is_some_and(|report| report.below_threshold);
                     }
                 }
 
-                // Return conversion result
-                Some(ConversionResult {
-                    original_name: name,
-                    converted_name: output_name,
-                    original_size,
-                    converted_size: converted_data.len() as u64,
-                    saved_path: output_path.to_string_lossy().to_string(),
-                })
+                // Return one conversion result per output
+                results
             })
             .await
-            .ok()
-            .flatten();
+            .unwrap_or_default();
 
             // Send result with index to channel
             let _ = result_tx.send((index, result)).await;
@@ -931,7 +898,7 @@ pub async fn convert_images(
     // Extract results in order
     let results: Vec<ConversionResult> = indexed_results
         .into_iter()
-        .filter_map(|(_, result)| result)
+        .flat_map(|(_, results)| results)
         .collect();
 
     // Mark converted files
@@ -949,3 +916,673 @@ pub async fn convert_images(
 
     Ok(results)
 }
+
+// Animated sources keep every frame for targets that can store animations
+// (still targets and first frame only mode use the decoded still image)
+fn keeps_animation(options: &ConversionOptions) -> bool {
+    !options.first_frame_only && supports_animation(&options.target_format)
+}
+
+// Output file name from the spec's template, {stem} = source (page) stem, {ext} = target format
+fn output_name(spec: &OutputSpec, stem: &str) -> String {
+    spec.name
+        .replace("{stem}", stem)
+        .replace("{ext}", &spec.options.target_format)
+}
+
+// Output specs must not write the same file name twice for one source,
+// including across the pages of a multi-page source, or outside the output directory
+fn check_output_names(outputs: &[OutputSpec], stems: &[&str]) -> Result<(), String> {
+    let mut names = std::collections::HashSet::new();
+    for spec in outputs {
+        for stem in stems {
+            let name = output_name(spec, stem);
+            if std::path::Path::new(&name).file_name() != Some(std::ffi::OsStr::new(&name)) {
+                return Err(format!("Output name is not a plain file name: {}", name));
+            }
+            if !names.insert(name.clone()) {
+                return Err(format!("Output specs share the file name {}", name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn generate_favicon_set(
+    id: String,
+    options: Option<FaviconOptions>,
+    output_dir: String,
+    create_subfolder: bool,
+    subfolder_name: String,
+    url_files_fallback_dir: String,
+    window: tauri::Window,
+    state: tauri::State<'_, FileListState>,
+) -> Result<Vec<ConversionResult>, String> {
+    let options = options.unwrap_or_default();
+    let (name, source_path, data) = {
+        let file_list = state.0.lock().unwrap();
+        let file = file_list
+            .iter()
+            .find(|f| f.id == id)
+            .ok_or_else(|| "File not found".to_string())?;
+        (
+            file.name.clone(),
+            file.source_path.clone(),
+            file.data.clone(),
+        )
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let emit = |status: &str, error_message: Option<String>, saved_path: Option<String>| {
+            let _ = window.emit(
+                "conversion-progress",
+                ConversionProgress {
+                    file_id: id.clone(),
+                    file_name: name.clone(),
+                    status: status.to_string(),
+                    error_message,
+                    saved_path,
+                    quality_report: None,
+                    progress: None,
+                },
+            );
+        };
+
+        emit("converting", None, None);
+
+        let result = (|| -> Result<Vec<ConversionResult>, String> {
+            let stem = std::path::Path::new(&name)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("image");
+
+            let img = decode_source(&data, &RawOptions::default())?;
+            let files = favicon_set(&img, stem, &options)?;
+
+            // Whole set goes into its own folder, never overwriting an earlier set
+            let folder = conversion_output_dir(
+                &output_dir,
+                source_path.as_deref(),
+                create_subfolder,
+                &subfolder_name,
+                &url_files_fallback_dir,
+            )?
+            .join(format!("{}-favicon", stem));
+            std::fs::create_dir_all(&folder)
+                .map_err(|e| format!("Failed to create folder: {}", e))?;
+            if let Some((file_name, _)) = files.iter().find(|(f, _)| folder.join(f).exists()) {
+                return Err(format!("File already exists: {}", file_name));
+            }
+
+            // A failed write leaves no partial set (and no new empty folder) behind
+            let paths = write_all(&folder, &files).inspect_err(|_| {
+                let _ = std::fs::remove_dir(&folder);
+            })?;
+
+            let results = files
+                .iter()
+                .zip(paths)
+                .map(|((file_name, contents), output_path)| ConversionResult {
+                    original_name: name.clone(),
+                    converted_name: file_name.to_string(),
+                    original_size: data.len() as u64,
+                    converted_size: contents.len() as u64,
+                    saved_path: output_path.to_string_lossy().to_string(),
+                    chosen_quality: None,
+                    resized_to: None,
+                    dssim: None,
+                    quality_report: None,
+                    srcset: None,
+                })
+                .collect();
+
+            Ok(results)
+        })();
+
+        match result {
+            Ok(results) => {
+                let saved_path = results.last().map(|r| r.saved_path.clone());
+                emit("completed", None, saved_path);
+                Ok(results)
+            }
+            Err(e) => {
+                emit("error", Some(e.clone()), None);
+                eprintln!("{}", e);
+                Err(e)
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("Favicon task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn combine_files(
+    ids: Vec<String>,
+    options: Option<CombineOptions>,
+    output_dir: String,
+    window: tauri::Window,
+    state: tauri::State<'_, FileListState>,
+) -> Result<ConversionResult, String> {
+    let options = options.unwrap_or_default();
+    let extension = match options.format.as_str() {
+        "pdf" => "pdf",
+        "tiff" | "tif" => "tif",
+        other => return Err(format!("Unsupported combine format: {}", other)),
+    };
+
+    // (id, name, source path, data) in list order, not selection order
+    let files: Vec<(String, String, Option<String>, Vec<u8>)> = {
+        let file_list = state.0.lock().unwrap();
+        file_list
+            .iter()
+            .filter(|f| ids.contains(&f.id))
+            .map(|f| {
+                (
+                    f.id.clone(),
+                    f.name.clone(),
+                    f.source_path.clone(),
+                    f.data.clone(),
+                )
+            })
+            .collect()
+    };
+    if ids.is_empty() {
+        return Err("No files selected".to_string());
+    }
+    // A page missing from the document must not go unnoticed
+    let missing: Vec<&str> = ids
+        .iter()
+        .filter(|id| !files.iter().any(|(file_id, _, _, _)| file_id == *id))
+        .map(|id| id.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Files not found: {}", missing.join(", ")));
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        let emit = |id: &str,
+                    name: &str,
+                    status: &str,
+                    error_message: Option<String>,
+                    saved_path: Option<String>| {
+            let _ = window.emit(
+                "conversion-progress",
+                ConversionProgress {
+                    file_id: id.to_string(),
+                    file_name: name.to_string(),
+                    status: status.to_string(),
+                    error_message,
+                    saved_path,
+                    quality_report: None,
+                    progress: None,
+                },
+            );
+        };
+
+        let result = (|| -> Result<ConversionResult, String> {
+            let (_, first_name, first_source, _) = &files[0];
+            let file_name = if options.file_name.is_empty() {
+                let stem = std::path::Path::new(first_name)
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("image");
+                format!("{}-combined.{}", stem, extension)
+            } else {
+                format!("{}.{}", options.file_name, extension)
+            };
+            let output_path =
+                resolve_output_dir(&output_dir, first_source.as_deref())?.join(&file_name);
+            if output_path.exists() {
+                return Err(format!("File already exists: {}", file_name));
+            }
+
+            // Pages are decoded one at a time while the document is written
+            let pages = files.iter().map(|(id, name, _, data)| {
+                emit(id, name, "converting", None, None);
+                decode_source(data, &RawOptions::default()).map_err(|e| format!("{}: {}", name, e))
+            });
+            let contents = match extension {
+                "pdf" => combine_to_pdf(pages, &options)?,
+                _ => combine_to_tiff(pages, &options)?,
+            };
+
+            std::fs::write(&output_path, &contents)
+                .map_err(|e| format!("Failed to write file: {}", e))?;
+
+            Ok(ConversionResult {
+                original_name: first_name.clone(),
+                converted_name: file_name,
+                original_size: files.iter().map(|(_, _, _, data)| data.len() as u64).sum(),
+                converted_size: contents.len() as u64,
+                saved_path: output_path.to_string_lossy().to_string(),
+                chosen_quality: None,
+                resized_to: None,
+                dssim: None,
+                quality_report: None,
+                srcset: None,
+            })
+        })();
+
+        // Every page shares the outcome of the combined file
+        for (id, name, _, _) in &files {
+            match &result {
+                Ok(result) => emit(id, name, "completed", None, Some(result.saved_path.clone())),
+                Err(e) => emit(id, name, "error", Some(e.clone()), None),
+            }
+        }
+        if let Err(e) = &result {
+            eprintln!("{}", e);
+        }
+
+        result.map(|result| (ids, result))
+    })
+    .await
+    .map_err(|e| format!("Combine task failed: {}", e))?;
+
+    let (ids, result) = result?;
+    {
+        let mut file_list = state.0.lock().unwrap();
+        for file in file_list.iter_mut().filter(|f| ids.contains(&f.id)) {
+            file.converted = true;
+            file.converted_path = Some(result.saved_path.clone());
+            file.output_paths = vec![result.saved_path.clone()];
+            file.below_quality_threshold = false;
+        }
+    }
+
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn set_ffmpeg_path(
+    path: Option<String>,
+    video_state: tauri::State<VideoState>,
+) -> Result<(), String> {
+    // Empty string resets to the lookup next to the executable / on PATH
+    let path = path.filter(|p| !p.is_empty()).map(PathBuf::from);
+    if let Some(ref path) = path {
+        locate_ffmpeg(Some(path))?;
+    }
+
+    *video_state.ffmpeg_path.lock().unwrap() = path;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_ffmpeg_path(video_state: tauri::State<VideoState>) -> Result<String, String> {
+    let configured = video_state.ffmpeg_path.lock().unwrap().clone();
+    let path = locate_ffmpeg(configured.as_deref())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn probe_video_file(
+    id: String,
+    state: tauri::State<'_, FileListState>,
+    video_state: tauri::State<'_, VideoState>,
+) -> Result<VideoInfo, String> {
+    let (name, source_path, data) = video_source(&state, &id)?;
+    let configured = video_state.ffmpeg_path.lock().unwrap().clone();
+
+    tokio::task::spawn_blocking(move || {
+        let ffprobe = locate_ffprobe(configured.as_deref())?;
+        let input = VideoInput::new(source_path.as_deref(), &data, &name)?;
+        probe_video(&ffprobe, input.path())
+    })
+    .await
+    .map_err(|e| format!("Probe task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn transcode_video(
+    id: String,
+    options: VideoOptions,
+    output_dir: String,
+    window: tauri::Window,
+    state: tauri::State<'_, FileListState>,
+    video_state: tauri::State<'_, VideoState>,
+) -> Result<ConversionResult, String> {
+    let (name, source_path, data) = video_source(&state, &id)?;
+    let original_size = {
+        let file_list = state.0.lock().unwrap();
+        file_list.iter().find(|f| f.id == id).map_or(0, |f| f.size)
+    };
+    let configured = video_state.ffmpeg_path.lock().unwrap().clone();
+    let jobs = video_state.jobs.clone();
+    let state_arc = state.0.clone();
+    // Held until the blocking task ends, a second job for the file is rejected
+    let job = start_job(&jobs, &id)?;
+
+    tokio::task::spawn_blocking(move || {
+        let _job = job;
+        let emit = |status: &str,
+                    error_message: Option<String>,
+                    saved_path: Option<String>,
+                    progress: Option<f64>| {
+            let _ = window.emit(
+                "conversion-progress",
+                ConversionProgress {
+                    file_id: id.clone(),
+                    file_name: name.clone(),
+                    status: status.to_string(),
+                    error_message,
+                    saved_path,
+                    quality_report: None,
+                    progress,
+                },
+            );
+        };
+
+        let result = (|| -> Result<(TranscodeOutcome, String, PathBuf), String> {
+            let ffmpeg = locate_ffmpeg(configured.as_deref())?;
+            let input = VideoInput::new(source_path.as_deref(), &data, &name)?;
+
+            let output_dir = resolve_output_dir(&output_dir, source_path.as_deref())?;
+            let output_name = format!(
+                "{}.{}",
+                std::path::Path::new(&name)
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("video"),
+                output_extension(&options.target_format)
+            );
+            let output_path = output_dir.join(&output_name);
+            if output_path.exists() {
+                return Err("File already exists".to_string());
+            }
+
+            // Duration is only needed for progress, conversion works without ffprobe
+            let duration = locate_ffprobe(configured.as_deref())
+                .and_then(|ffprobe| probe_video(&ffprobe, input.path()))
+                .ok()
+                .and_then(|info| info.duration);
+
+            emit("converting", None, None, Some(0.0));
+            let outcome = transcode(
+                &ffmpeg,
+                input.path(),
+                &output_path,
+                &options,
+                duration,
+                &jobs,
+                &id,
+                |progress| emit("converting", None, None, Some(progress)),
+            )?;
+
+            Ok((outcome, output_name, output_path))
+        })();
+
+        match result {
+            Ok((TranscodeOutcome::Completed, output_name, output_path)) => {
+                let saved_path = output_path.to_string_lossy().to_string();
+                emit("completed", None, Some(saved_path.clone()), Some(1.0));
+
+                // Mark file as converted and store converted path
+                {
+                    let mut file_list = state_arc.lock().unwrap();
+                    if let Some(file) = file_list.iter_mut().find(|f| f.id == id) {
+                        file.converted = true;
+                        file.converted_path = Some(saved_path.clone());
+                    }
+                }
+
+                Ok(ConversionResult {
+                    original_name: name.clone(),
+                    converted_name: output_name,
+                    original_size,
+                    converted_size: std::fs::metadata(&output_path).map_or(0, |m| m.len()),
+                    saved_path,
+                    chosen_quality: None,
+                    resized_to: None,
+                    dssim: None,
+                    quality_report: None,
+                    srcset: None,
+                })
+            }
+            Ok((TranscodeOutcome::Cancelled, _, _)) => {
+                emit("cancelled", None, None, None);
+                Err("Conversion cancelled".to_string())
+            }
+            Err(e) => {
+                emit("error", Some(e.clone()), None, None);
+                eprintln!("{}", e);
+                Err(e)
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("Video task failed: {}", e))?
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn extract_video_frames(
+    id: String,
+    options: ConversionOptions,
+    timestamps: Option<Vec<f64>>, // Seconds, takes precedence over interval
+    interval: Option<f64>,        // Seconds between frames over the whole clip
+    output_dir: String,
+    window: tauri::Window,
+    state: tauri::State<'_, FileListState>,
+    video_state: tauri::State<'_, VideoState>,
+) -> Result<Vec<ConversionResult>, String> {
+    let (name, source_path, data) = video_source(&state, &id)?;
+    let configured = video_state.ffmpeg_path.lock().unwrap().clone();
+    let jobs = video_state.jobs.clone();
+    let job = start_job(&jobs, &id)?;
+
+    tokio::task::spawn_blocking(move || {
+        let _job = job;
+        let emit = |status: &str,
+                    error_message: Option<String>,
+                    saved_path: Option<String>,
+                    progress: Option<f64>| {
+            let _ = window.emit(
+                "conversion-progress",
+                ConversionProgress {
+                    file_id: id.clone(),
+                    file_name: name.clone(),
+                    status: status.to_string(),
+                    error_message,
+                    saved_path,
+                    quality_report: None,
+                    progress,
+                },
+            );
+        };
+
+        let result = (|| -> Result<Option<Vec<ConversionResult>>, String> {
+            let ffmpeg = locate_ffmpeg(configured.as_deref())?;
+            let input = VideoInput::new(source_path.as_deref(), &data, &name)?;
+
+            let timestamps = match (timestamps, interval) {
+                (Some(timestamps), _) => {
+                    check_timestamps(&timestamps)?;
+                    timestamps
+                }
+                (None, Some(interval)) => {
+                    let ffprobe = locate_ffprobe(configured.as_deref())?;
+                    let duration = probe_video(&ffprobe, input.path())?
+                        .duration
+                        .ok_or_else(|| "Video duration is unknown".to_string())?;
+                    interval_timestamps(interval, duration)?
+                }
+                (None, None) => return Err("Specify timestamps or an interval".to_string()),
+            };
+
+            let stem = std::path::Path::new(&name)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("video");
+            let output_dir = resolve_output_dir(&output_dir, source_path.as_deref())?;
+
+            // Every target is checked before the first frame is decoded,
+            // so an existing file cannot stop the extraction halfway
+            let output_names: Vec<String> = timestamps
+                .iter()
+                .map(|&timestamp| {
+                    format!(
+                        "{}_{}ms.{}",
+                        stem,
+                        (timestamp * 1000.0).round() as u64,
+                        options.target_format
+                    )
+                })
+                .collect();
+            let mut seen = std::collections::HashSet::new();
+            for output_name in &output_names {
+                if !seen.insert(output_name) {
+                    return Err(format!("Duplicate frame timestamp: {}", output_name));
+                }
+                if output_dir.join(output_name).exists() {
+                    return Err(format!("File already exists: {}", output_name));
+                }
+            }
+
+            emit("converting", None, None, Some(0.0));
+
+            let mut results = Vec::new();
+            for (index, (&timestamp, output_name)) in
+                timestamps.iter().zip(output_names).enumerate()
+            {
+                let Some(frame) = extract_frame(&ffmpeg, input.path(), timestamp, &jobs, &id)?
+                else {
+                    return Ok(None);
+                };
+
+                // Same decode / encode path as convert_images
+                let img = decode_image(&frame)?;
+                let encoded = encode_image(&img, &options, None)?;
+
+                let output_path = output_dir.join(&output_name);
+                std::fs::write(&output_path, &encoded.data)
+                    .map_err(|e| format!("Failed to write file: {}", e))?;
+
+                emit(
+                    "converting",
+                    None,
+                    None,
+                    Some((index + 1) as f64 / timestamps.len() as f64),
+                );
+
+                results.push(ConversionResult {
+                    original_name: name.clone(),
+                    converted_name: output_name,
+                    original_size: frame.len() as u64,
+                    converted_size: encoded.data.len() as u64,
+                    saved_path: output_path.to_string_lossy().to_string(),
+                    chosen_quality: encoded.quality,
+                    resized_to: encoded.resized_to,
+                    dssim: encoded.dssim,
+                    quality_report: None,
+                    srcset: None,
+                });
+            }
+
+            Ok(Some(results))
+        })();
+
+        match result {
+            Ok(Some(results)) => {
+                let saved_path = results.last().map(|r| r.saved_path.clone());
+                emit("completed", None, saved_path, Some(1.0));
+                Ok(results)
+            }
+            Ok(None) => {
+                emit("cancelled", None, None, None);
+                Err("Frame extraction cancelled".to_string())
+            }
+            Err(e) => {
+                emit("error", Some(e.clone()), None, None);
+                eprintln!("{}", e);
+                Err(e)
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("Frame extraction task failed: {}", e))?
+}
+
+#[tauri::command]
+pub fn cancel_video_conversion(
+    id: String,
+    video_state: tauri::State<VideoState>,
+) -> Result<(), String> {
+    if cancel_job(&video_state.jobs, &id) {
+        Ok(())
+    } else {
+        Err("No running conversion for this file".to_string())
+    }
+}
+
+// Name, source path and (for URL files) bytes of a video file item
+fn video_source(
+    state: &FileListState,
+    id: &str,
+) -> Result<(String, Option<String>, Vec<u8>), String> {
+    let file_list = state.0.lock().unwrap();
+    let file = file_list
+        .iter()
+        .find(|f| f.id == id)
+        .ok_or_else(|| "File not found".to_string())?;
+
+    // Files on disk are read by ffmpeg directly, only URL files need their bytes
+    let data = if file.source_path.is_some() {
+        Vec::new()
+    } else {
+        file.data.clone()
+    };
+
+    Ok((file.name.clone(), file.source_path.clone(), data))
+}
+
+// Output directory of a conversion: the source directory for USE_SOURCE_DIR
+// (the fallback directory, or Downloads when empty, for URL files),
+// plus the subfolder when enabled
+fn conversion_output_dir(
+    output_dir: &str,
+    source_path: Option<&str>,
+    create_subfolder: bool,
+    subfolder_name: &str,
+    url_files_fallback_dir: &str,
+) -> Result<PathBuf, String> {
+    if output_dir != "USE_SOURCE_DIR" {
+        return Ok(PathBuf::from(output_dir));
+    }
+
+    let dir = match source_path {
+        Some(path) => std::path::Path::new(path)
+            .parent()
+            .map(|dir| dir.to_path_buf())
+            .ok_or_else(|| "Failed to get source directory".to_string())?,
+        None if url_files_fallback_dir.is_empty() => {
+            dirs::download_dir().ok_or_else(|| "Cannot determine Downloads folder".to_string())?
+        }
+        None => PathBuf::from(url_files_fallback_dir),
+    };
+
+    if !create_subfolder || subfolder_name.is_empty() {
+        return Ok(dir);
+    }
+    let subfolder = dir.join(subfolder_name);
+    if !subfolder.exists() {
+        std::fs::create_dir_all(&subfolder)
+            .map_err(|e| format!("Failed to create subfolder: {}", e))?;
+    }
+    Ok(subfolder)
+}
+
+// Output next to the source for USE_SOURCE_DIR, Downloads for URL files
+fn resolve_output_dir(output_dir: &str, source_path: Option<&str>) -> Result<PathBuf, String> {
+    if output_dir != "USE_SOURCE_DIR" {
+        return Ok(PathBuf::from(output_dir));
+    }
+
+    match source_path.and_then(|p| std::path::Path::new(p).parent()) {
+        Some(dir) => Ok(dir.to_path_buf()),
+        None => dirs::download_dir().ok_or_else(|| "Cannot determine Downloads folder".to_string()),
+    }
+}
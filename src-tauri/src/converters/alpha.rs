@@ -0,0 +1,91 @@
+use crate::models::ConversionOptions;
+
+use super::depth::{is_float, is_high_depth, srgb_to_linear, Rgb16Image};
+
+// Target formats that cannot store an alpha channel
+fn supports_alpha(target_format: &str) -> bool {
+    !matches!(target_format, "jpeg" | "jpg" | "pnm" | "hdr")
+}
+
+// Flatten transparent images over the background color when the target needs it
+// Returns None when the image can be encoded as-is
+pub fn flatten_for_target(
+    img: &image::DynamicImage,
+    options: &ConversionOptions,
+) -> Option<image::DynamicImage> {
+    if !img.has_alpha() {
+        return None;
+    }
+
+    if options.flatten_alpha || !supports_alpha(&options.target_format) {
+        return Some(flatten_to_rgb(img, options.background));
+    }
+
+    None
+}
+
+// Composite over background color at the image's own depth (8-bit, 16-bit or float RGB)
+pub fn flatten_to_rgb(img: &image::DynamicImage, background: [u8; 3]) -> image::DynamicImage {
+    // Float images reach this only for HDR / OpenEXR targets, keep them unclipped
+    if is_float(img) {
+        return image::DynamicImage::ImageRgb32F(flatten_alpha32f(img, background));
+    }
+    // Keep 16-bit precision, encoders reduce depth themselves when needed
+    if is_high_depth(img) {
+        return image::DynamicImage::ImageRgb16(flatten_alpha16(img, background));
+    }
+    image::DynamicImage::ImageRgb8(flatten_alpha(img, background))
+}
+
+// Composite image over background color (straight alpha)
+pub fn flatten_alpha(img: &image::DynamicImage, background: [u8; 3]) -> image::RgbImage {
+    let rgba_img = img.to_rgba8();
+    image::RgbImage::from_fn(rgba_img.width(), rgba_img.height(), |x, y| {
+        let [r, g, b, a] = rgba_img.get_pixel(x, y).0;
+        let alpha = a as u32;
+        let blend =
+            |c: u8, bg: u8| ((c as u32 * alpha + bg as u32 * (255 - alpha) + 127) / 255) as u8;
+
+        image::Rgb([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+        ])
+    })
+}
+
+// Composite 16-bit image over background color (straight alpha)
+fn flatten_alpha16(img: &image::DynamicImage, background: [u8; 3]) -> Rgb16Image {
+    let rgba_img = img.to_rgba16();
+    Rgb16Image::from_fn(rgba_img.width(), rgba_img.height(), |x, y| {
+        let [r, g, b, a] = rgba_img.get_pixel(x, y).0;
+        let alpha = a as u64;
+        let blend = |c: u16, bg: u8| {
+            let bg = bg as u64 * 257;
+            ((c as u64 * alpha + bg * (65535 - alpha) + 32767) / 65535) as u16
+        };
+
+        image::Rgb([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+        ])
+    })
+}
+
+// Composite linear float image over background color (blended in linear light)
+fn flatten_alpha32f(img: &image::DynamicImage, background: [u8; 3]) -> image::Rgb32FImage {
+    let rgba_img = img.to_rgba32f();
+    let background = background.map(|c| srgb_to_linear(c as f32 / 255.0));
+    image::Rgb32FImage::from_fn(rgba_img.width(), rgba_img.height(), |x, y| {
+        let [r, g, b, a] = rgba_img.get_pixel(x, y).0;
+        let alpha = a.clamp(0.0, 1.0);
+        let blend = |c: f32, bg: f32| c * alpha + bg * (1.0 - alpha);
+
+        image::Rgb([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+        ])
+    })
+}
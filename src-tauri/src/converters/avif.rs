@@ -1,32 +1,174 @@
 use rgb::FromSlice;
 
-// Convert to AVIF using ravif with configurable speed
+use crate::models::AvifOptions;
+
+use super::avif_subsampled::{encode_avif_420, to_ycbcr};
+use super::depth::{is_high_depth, reduce_to_8bit, to_rgb8_dithered, to_rgba8_dithered};
+
+// Convert to AVIF using ravif with configurable speed, bit depth, alpha and color options
+// 4:2:0 chroma subsampling goes through rav1e directly (ravif always encodes 4:4:4)
 pub fn convert_to_avif(
     img: &image::DynamicImage,
     quality: u8,
     speed: u8,
+    options: &AvifOptions,
     _exif_bytes: Option<&[u8]>, // EXIF not supported yet for AVIF
 ) -> Result<Vec<u8>, String> {
-    let rgba_img = img.to_rgba8();
-    let (width, height) = rgba_img.dimensions();
-
-    // Convert to rgb crate's RGBA format
-    let raw_pixels = rgba_img.as_raw();
-    let rgba_pixels: &[rgb::RGBA8] = raw_pixels.as_rgba();
+    // ravif asserts quality in 1-100
+    let quality = quality.clamp(1, 100);
+    let alpha_quality = options.alpha_quality.unwrap_or(quality).clamp(1, 100);
 
     // Clamp speed to valid range (1-10)
     let speed = speed.clamp(1, 10);
 
-    // Create ravif encoder with user-configurable speed
+    let bit_depth = match options.bit_depth {
+        8 => ravif::BitDepth::Eight,
+        10 => ravif::BitDepth::Ten,
+        depth => return Err(format!("Unsupported AVIF bit depth: {}", depth)),
+    };
+
+    let alpha_color_mode = match options.alpha_color_mode.as_str() {
+        "clean" => ravif::AlphaColorMode::UnassociatedClean,
+        "dirty" => ravif::AlphaColorMode::UnassociatedDirty,
+        "premultiplied" => ravif::AlphaColorMode::Premultiplied,
+        mode => return Err(format!("Unsupported AVIF alpha color mode: {}", mode)),
+    };
+
+    let color_model = match options.color_model.as_str() {
+        "ycbcr" => ravif::ColorModel::YCbCr,
+        "rgb" => ravif::ColorModel::RGB,
+        model => return Err(format!("Unsupported AVIF color model: {}", model)),
+    };
+
+    match options.chroma_subsampling.as_str() {
+        "444" => {}
+        "420" => {
+            // AV1 identity matrix (RGB) is only valid without subsampling
+            if options.color_model == "rgb" {
+                return Err("AVIF RGB color model requires 4:4:4 chroma subsampling".to_string());
+            }
+
+            // 8-bit output dithers deeper sources first, 8-bit input widens exactly
+            let mut rgba_img = match reduce_to_8bit(img).filter(|_| options.bit_depth == 8) {
+                Some(reduced) => reduced.to_rgba16(),
+                None => img.to_rgba16(),
+            };
+            // Clean mode: drop color of fully transparent pixels so it compresses away
+            if options.alpha_color_mode == "clean" {
+                for px in rgba_img.pixels_mut() {
+                    if px.0[3] == 0 {
+                        px.0 = [0, 0, 0, 0];
+                    }
+                }
+            }
+
+            return encode_avif_420(
+                &rgba_img,
+                quality,
+                alpha_quality,
+                speed,
+                options.bit_depth,
+                options.alpha_color_mode == "premultiplied",
+            );
+        }
+        sampling => return Err(format!("Unsupported AVIF chroma subsampling: {}", sampling)),
+    }
+
+    // Create ravif encoder with user-configurable options
     let encoder = ravif::Encoder::new()
         .with_quality(quality as f32)
+        .with_alpha_quality(alpha_quality as f32)
         .with_speed(speed) // 1-10: lower = better compression, higher = faster
+        .with_bit_depth(bit_depth)
+        .with_internal_color_model(color_model)
+        .with_alpha_color_mode(alpha_color_mode)
         .with_num_threads(Some(num_cpus::get()));
 
-    // Encode to AVIF
-    let avif_data = encoder
-        .encode_rgba(ravif::Img::new(rgba_pixels, width as usize, height as usize))
-        .map_err(|e| format!("AVIF encoding failed: {}", e))?;
+    // ravif's pixel API takes 8-bit input only, so deep sources bypass it for 10-bit output
+    let avif_data = if options.bit_depth == 10 && is_high_depth(img) {
+        encode_planes_10bit(&encoder, img, options)
+    } else if img.color().has_alpha() {
+        // Opaque images skip the alpha channel entirely
+        let rgba_img = to_rgba8_dithered(img);
+        let (width, height) = rgba_img.dimensions();
+
+        // Convert to rgb crate's RGBA format
+        let rgba_pixels: &[rgb::RGBA8] = rgba_img.as_raw().as_rgba();
+
+        encoder.encode_rgba(ravif::Img::new(
+            rgba_pixels,
+            width as usize,
+            height as usize,
+        ))
+    } else {
+        let rgb_img = to_rgb8_dithered(img);
+        let (width, height) = rgb_img.dimensions();
+
+        let rgb_pixels: &[rgb::RGB8] = rgb_img.as_raw().as_rgb();
+
+        encoder.encode_rgb(ravif::Img::new(rgb_pixels, width as usize, height as usize))
+    }
+    .map_err(|e| format!("AVIF encoding failed: {}", e))?;
 
     Ok(avif_data.avif_file)
 }
+
+// 4:4:4 planes straight from 16-bit samples, scaled to 10 bits
+// Alpha modes are applied here, ravif only handles them for its 8-bit input
+fn encode_planes_10bit(
+    encoder: &ravif::Encoder,
+    img: &image::DynamicImage,
+    options: &AvifOptions,
+) -> Result<ravif::EncodedImage, ravif::Error> {
+    let mut rgba_img = img.to_rgba16();
+    let (width, height) = rgba_img.dimensions();
+    if options.alpha_color_mode == "clean" {
+        for px in rgba_img.pixels_mut() {
+            if px.0[3] == 0 {
+                px.0 = [0, 0, 0, 0];
+            }
+        }
+    }
+
+    let premultiplied = options.alpha_color_mode == "premultiplied";
+    let rgb_model = options.color_model == "rgb";
+    let to_ten = |value: f32| (value * 1023.0).round().clamp(0.0, 1023.0) as u16;
+    let chroma_to_ten = |value: f32| (value * 1023.0 + 512.0).round().clamp(0.0, 1023.0) as u16;
+
+    let planes = rgba_img.pixels().map(|px| {
+        let [r, g, b, a] = px.0.map(|c| c as f32 / 65535.0);
+        let (r, g, b) = if premultiplied {
+            (r * a, g * a, b * a)
+        } else {
+            (r, g, b)
+        };
+        if rgb_model {
+            // AV1 identity matrix stores planes in G, B, R order
+            [to_ten(g), to_ten(b), to_ten(r)]
+        } else {
+            let [luma, cb, cr] = to_ycbcr(r, g, b);
+            [to_ten(luma), chroma_to_ten(cb), chroma_to_ten(cr)]
+        }
+    });
+
+    // Alpha plane only when something is actually transparent
+    let alpha = rgba_img
+        .pixels()
+        .any(|px| px.0[3] != u16::MAX)
+        .then(|| rgba_img.pixels().map(|px| to_ten(px.0[3] as f32 / 65535.0)));
+
+    let matrix_coefficients = if rgb_model {
+        ravif::MatrixCoefficients::Identity
+    } else {
+        ravif::MatrixCoefficients::BT601
+    };
+
+    encoder.encode_raw_planes_10_bit(
+        width as usize,
+        height as usize,
+        planes,
+        alpha,
+        rav1e::prelude::PixelRange::Full,
+        matrix_coefficients,
+    )
+}
@@ -0,0 +1,374 @@
+use rav1e::prelude::*;
+
+use super::depth::Rgba16Image;
+
+// BT.601 luma coefficients (same matrix ravif uses for YCbCr)
+const KR: f32 = 0.299;
+const KG: f32 = 0.587;
+const KB: f32 = 0.114;
+
+// Planar 4:2:0 input for the AV1 encoder
+struct Planes {
+    y: Vec<u16>,
+    u: Vec<u16>,
+    v: Vec<u16>,
+    alpha: Option<Vec<u16>>,
+}
+
+// Encode RGBA16 pixels as 4:2:0 AVIF via rav1e
+// ravif (0.11) always configures rav1e for 4:4:4 and has no subsampling option,
+// so the planes are built here and the AV1 streams wrapped with avif-serialize
+// 16-bit input keeps full precision for 10-bit output
+// Alpha is stored as a separate monochrome AV1 image, like ravif does
+pub fn encode_avif_420(
+    rgba_img: &Rgba16Image,
+    quality: u8,
+    alpha_quality: u8,
+    speed: u8,
+    bit_depth: u8,
+    premultiplied: bool,
+) -> Result<Vec<u8>, String> {
+    let (width, height) = rgba_img.dimensions();
+    let planes = to_planes(rgba_img, bit_depth, premultiplied);
+
+    let (color, alpha) = if bit_depth > 8 {
+        encode_planes::<u16>(
+            &planes,
+            width,
+            height,
+            quality,
+            alpha_quality,
+            speed,
+            bit_depth,
+        )?
+    } else {
+        encode_planes::<u8>(
+            &planes,
+            width,
+            height,
+            quality,
+            alpha_quality,
+            speed,
+            bit_depth,
+        )?
+    };
+
+    let mut aviffy = avif_serialize::Aviffy::new();
+    aviffy
+        .set_seq_profile(0) // Main profile (4:2:0), default assumes 4:4:4
+        .set_chroma_subsampling((true, true))
+        .set_full_color_range(true)
+        .set_matrix_coefficients(avif_serialize::constants::MatrixCoefficients::Bt601)
+        .set_premultiplied_alpha(premultiplied && alpha.is_some());
+
+    Ok(aviffy.to_vec(&color, alpha.as_deref(), width, height, bit_depth))
+}
+
+// Convert RGBA16 to full range YCbCr planes, averaging chroma over 2x2 blocks
+fn to_planes(rgba_img: &Rgba16Image, bit_depth: u8, premultiplied: bool) -> Planes {
+    let (width, height) = rgba_img.dimensions();
+    let (width, height) = (width as usize, height as usize);
+    let (chroma_w, chroma_h) = (width.div_ceil(2), height.div_ceil(2));
+
+    // Samples are normalized to 0.0 - 1.0, chroma centered on 0.0
+    let max_value = ((1u32 << bit_depth) - 1) as f32;
+    let chroma_offset = (1u32 << (bit_depth - 1)) as f32;
+    let to_depth = |value: f32| (value * max_value).round().clamp(0.0, max_value) as u16;
+    let chroma_to_depth = |value: f32| {
+        (value * max_value + chroma_offset)
+            .round()
+            .clamp(0.0, max_value) as u16
+    };
+
+    let mut y = Vec::with_capacity(width * height);
+    let mut cb_sum = vec![0.0f32; chroma_w * chroma_h];
+    let mut cr_sum = vec![0.0f32; chroma_w * chroma_h];
+    let mut counts = vec![0u8; chroma_w * chroma_h];
+
+    for (x, row, px) in rgba_img.enumerate_pixels() {
+        let [r, g, b, a] = px.0.map(|c| c as f32 / 65535.0);
+        let (r, g, b) = if premultiplied {
+            (r * a, g * a, b * a)
+        } else {
+            (r, g, b)
+        };
+
+        let [luma, cb, cr] = to_ycbcr(r, g, b);
+        y.push(to_depth(luma));
+
+        let index = (row as usize / 2) * chroma_w + x as usize / 2;
+        cb_sum[index] += cb;
+        cr_sum[index] += cr;
+        counts[index] += 1;
+    }
+
+    let average = |sums: Vec<f32>| -> Vec<u16> {
+        sums.iter()
+            .zip(&counts)
+            .map(|(&sum, &count)| chroma_to_depth(sum / count.max(1) as f32))
+            .collect()
+    };
+
+    // Alpha plane only when something is actually transparent
+    let alpha = if rgba_img.pixels().any(|px| px.0[3] != u16::MAX) {
+        Some(
+            rgba_img
+                .pixels()
+                .map(|px| to_depth(px.0[3] as f32 / 65535.0))
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    Planes {
+        y,
+        u: average(cb_sum),
+        v: average(cr_sum),
+        alpha,
+    }
+}
+
+// Full range BT.601 YCbCr from normalized RGB, chroma centered on 0.0
+pub(super) fn to_ycbcr(r: f32, g: f32, b: f32) -> [f32; 3] {
+    let luma = KR * r + KG * g + KB * b;
+    [
+        luma,
+        (b - luma) * (0.5 / (1.0 - KB)),
+        (r - luma) * (0.5 / (1.0 - KR)),
+    ]
+}
+
+// Encode color (and optional alpha) planes to raw AV1 bitstreams
+fn encode_planes<P: Pixel + Default>(
+    planes: &Planes,
+    width: u32,
+    height: u32,
+    quality: u8,
+    alpha_quality: u8,
+    speed: u8,
+    bit_depth: u8,
+) -> Result<(Vec<u8>, Option<Vec<u8>>), String> {
+    let (width, height) = (width as usize, height as usize);
+
+    let color_config = av1_config(
+        width,
+        height,
+        quality,
+        speed,
+        bit_depth,
+        ChromaSampling::Cs420,
+        Some(ColorDescription {
+            color_primaries: ColorPrimaries::BT709,
+            transfer_characteristics: TransferCharacteristics::SRGB,
+            matrix_coefficients: MatrixCoefficients::BT601,
+        }),
+    );
+    let color = encode_frame::<P>(&color_config, |frame| {
+        let (chroma_w, chroma_h) = (width.div_ceil(2), height.div_ceil(2));
+        fill_plane(&mut frame.planes[0], &planes.y, width, height);
+        fill_plane(&mut frame.planes[1], &planes.u, chroma_w, chroma_h);
+        fill_plane(&mut frame.planes[2], &planes.v, chroma_w, chroma_h);
+    })?;
+
+    let alpha = match &planes.alpha {
+        Some(alpha) => {
+            let alpha_config = av1_config(
+                width,
+                height,
+                alpha_quality,
+                speed,
+                bit_depth,
+                ChromaSampling::Cs400,
+                None,
+            );
+            Some(encode_frame::<P>(&alpha_config, |frame| {
+                fill_plane(&mut frame.planes[0], alpha, width, height);
+            })?)
+        }
+        None => None,
+    };
+
+    Ok((color, alpha))
+}
+
+// Still picture encoder config (full range, quality mapped like ravif)
+fn av1_config(
+    width: usize,
+    height: usize,
+    quality: u8,
+    speed: u8,
+    bit_depth: u8,
+    chroma_sampling: ChromaSampling,
+    color_description: Option<ColorDescription>,
+) -> Config {
+    let quantizer = quality_to_quantizer(quality);
+
+    let mut enc = EncoderConfig::with_speed_preset(speed.clamp(1, 10));
+    enc.width = width;
+    enc.height = height;
+    enc.bit_depth = bit_depth as usize;
+    enc.chroma_sampling = chroma_sampling;
+    enc.pixel_range = PixelRange::Full;
+    enc.color_description = color_description;
+    enc.still_picture = true;
+    enc.quantizer = quantizer as usize;
+    enc.min_quantizer = quantizer;
+    enc.bitrate = 0;
+    enc.tune = Tune::Psychovisual;
+
+    Config::new()
+        .with_encoder_config(enc)
+        .with_threads(num_cpus::get())
+}
+
+// Encode a single key frame and collect its packet data
+fn encode_frame<P: Pixel + Default>(
+    config: &Config,
+    init: impl FnOnce(&mut Frame<P>),
+) -> Result<Vec<u8>, String> {
+    let mut ctx: Context<P> = config
+        .new_context()
+        .map_err(|e| format!("AVIF encoding failed: {}", e))?;
+
+    let mut frame = ctx.new_frame();
+    init(&mut frame);
+    ctx.send_frame(frame)
+        .map_err(|e| format!("AVIF encoding failed: {}", e))?;
+    ctx.flush();
+
+    let mut out = Vec::new();
+    loop {
+        match ctx.receive_packet() {
+            Ok(mut packet) => {
+                if packet.frame_type == FrameType::KEY {
+                    out.append(&mut packet.data);
+                }
+            }
+            Err(EncoderStatus::Encoded) | Err(EncoderStatus::LimitReached) => break,
+            Err(e) => return Err(format!("AVIF encoding failed: {}", e)),
+        }
+    }
+
+    Ok(out)
+}
+
+// Copy row-major samples into a frame plane
+fn fill_plane<P: Pixel + Default>(plane: &mut Plane<P>, data: &[u16], width: usize, height: usize) {
+    let mut slice = plane.mut_slice(Default::default());
+    for (row, samples) in slice
+        .rows_iter_mut()
+        .zip(data.chunks_exact(width))
+        .take(height)
+    {
+        for (dst, &src) in row[..width].iter_mut().zip(samples) {
+            *dst = P::cast_from(src);
+        }
+    }
+}
+
+// Same quality (1-100) to quantizer (0-255) curve as ravif, so both paths match
+fn quality_to_quantizer(quality: u8) -> u8 {
+    let q = quality.clamp(1, 100) as f32 / 100.0;
+    let x = if q >= 0.85 {
+        (1.0 - q) * 3.0
+    } else if q > 0.25 {
+        1.0 - 0.125 - q * 0.5
+    } else {
+        1.0 - q
+    };
+    (x * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::depth::is_high_depth;
+    use image::{DynamicImage, GenericImageView};
+
+    // Smooth gradient, odd sized so the last chroma blocks are partial
+    fn gradient(width: u32, height: u32, transparent_corner: bool) -> Rgba16Image {
+        Rgba16Image::from_fn(width, height, |x, y| {
+            let alpha = if transparent_corner && x < 4 && y < 4 {
+                0
+            } else {
+                u16::MAX
+            };
+            image::Rgba([
+                (x * 65535 / width) as u16,
+                (y * 65535 / height) as u16,
+                0x8000,
+                alpha,
+            ])
+        })
+    }
+
+    // av1C property bytes after the box type (4:2:0 color item first, then alpha)
+    fn av1c_flags(data: &[u8]) -> Vec<u8> {
+        // Only the metadata, the AV1 payload in mdat comes last
+        let mdat = data
+            .windows(4)
+            .position(|window| window == b"mdat")
+            .unwrap();
+        data[..mdat]
+            .windows(4)
+            .enumerate()
+            .filter(|(_, window)| *window == b"av1C")
+            .map(|(index, _)| data[index + 6])
+            .collect()
+    }
+
+    // Decode through avif-decode and return the largest 8-bit channel difference
+    // Color of fully transparent pixels is not compared
+    fn round_trip_error(source: &Rgba16Image, bit_depth: u8) -> u8 {
+        let data = encode_avif_420(source, 95, 95, 10, bit_depth, false).unwrap();
+        let decoded = crate::decode::decode_image(&data).unwrap();
+        assert_eq!(decoded.dimensions(), source.dimensions());
+
+        // Subsampled in both directions, alpha only when the source has transparency
+        let has_alpha = source.pixels().any(|px| px.0[3] != u16::MAX);
+        let flags = av1c_flags(&data);
+        assert_eq!(flags.len(), 1 + usize::from(has_alpha));
+        assert_eq!(flags[0] & 0b0001_1100, 0b0000_1100, "color is not 4:2:0");
+        assert_eq!(flags[0] & 0b0100_0000 != 0, bit_depth > 8);
+        assert_eq!(decoded.has_alpha(), has_alpha);
+        // 10-bit output decodes at 16 bits, it is not reduced to 8
+        assert_eq!(is_high_depth(&decoded), bit_depth > 8);
+
+        let expected = DynamicImage::ImageRgba16(source.clone()).to_rgba8();
+        let actual = decoded.to_rgba8();
+        expected
+            .pixels()
+            .zip(actual.pixels())
+            .flat_map(|(e, a)| {
+                let channels = if e.0[3] == 0 { 3..4 } else { 0..4 };
+                channels.map(move |c| e.0[c].abs_diff(a.0[c]))
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trips_opaque_and_transparent_images() {
+        for bit_depth in [8, 10] {
+            for transparent_corner in [false, true] {
+                let source = gradient(33, 17, transparent_corner);
+                let error = round_trip_error(&source, bit_depth);
+                assert!(error <= 12, "{}-bit: max error {}", bit_depth, error);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_single_pixel() {
+        let source = Rgba16Image::from_pixel(1, 1, image::Rgba([0xC000, 0x4000, 0x2000, u16::MAX]));
+        assert!(round_trip_error(&source, 10) <= 8);
+    }
+
+    #[test]
+    fn gray_stays_neutral() {
+        let source = Rgba16Image::from_pixel(8, 8, image::Rgba([0x8080, 0x8080, 0x8080, u16::MAX]));
+        let planes = to_planes(&source, 10, false);
+        assert!(planes.u.iter().chain(&planes.v).all(|&c| c == 512));
+    }
+}
@@ -0,0 +1,66 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat};
+
+use super::depth::{is_high_depth, reduce_to_8bit, to_rgb8_dithered, to_rgba8_dithered};
+
+// BMP: 8 bits per channel, alpha kept (BITMAPV4 header)
+pub fn convert_to_bmp(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let reduced = reduce_to_8bit(img);
+    write_image(reduced.as_ref().unwrap_or(img), ImageFormat::Bmp)
+}
+
+// TGA: 8 bits per channel, gray and alpha layouts kept
+pub fn convert_to_tga(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let reduced = reduce_to_8bit(img);
+    write_image(reduced.as_ref().unwrap_or(img), ImageFormat::Tga)
+}
+
+// QOI: RGB or RGBA at 8 bits per channel only
+pub fn convert_to_qoi(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let img = if img.has_alpha() {
+        DynamicImage::ImageRgba8(to_rgba8_dithered(img))
+    } else {
+        DynamicImage::ImageRgb8(to_rgb8_dithered(img))
+    };
+    write_image(&img, ImageFormat::Qoi)
+}
+
+// PNM: binary PGM for gray images, binary PPM otherwise, 16-bit sources keep their depth
+// (no alpha, transparent sources are flattened by encode_image)
+// Written directly, the image crate encoder only writes 16-bit samples as PAM
+pub fn convert_to_pnm(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let has_color = img.color().has_color();
+    let high_depth = is_high_depth(img);
+
+    let mut buffer = format!(
+        "{}\n{} {}\n{}\n",
+        if has_color { "P6" } else { "P5" },
+        img.width(),
+        img.height(),
+        if high_depth { 65535 } else { 255 }
+    )
+    .into_bytes();
+
+    // 16-bit samples are big-endian
+    match (has_color, high_depth) {
+        (false, false) => buffer.extend_from_slice(img.to_luma8().as_raw()),
+        (false, true) => buffer.extend(
+            img.to_luma16()
+                .as_raw()
+                .iter()
+                .flat_map(|v| v.to_be_bytes()),
+        ),
+        (true, false) => buffer.extend_from_slice(img.to_rgb8().as_raw()),
+        (true, true) => buffer.extend(img.to_rgb16().as_raw().iter().flat_map(|v| v.to_be_bytes())),
+    }
+
+    Ok(buffer)
+}
+
+fn write_image(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    img.write_to(&mut Cursor::new(&mut buffer), format)
+        .map_err(|e| format!("{:?} encoding failed: {}", format, e))?;
+    Ok(buffer)
+}
@@ -0,0 +1,186 @@
+use image::{DynamicImage, ImageBuffer, Luma, Rgb, Rgba};
+
+// 16-bit buffer types (image keeps its own aliases crate-private)
+pub type Gray16Image = ImageBuffer<Luma<u16>, Vec<u16>>;
+pub type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+pub type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+// 4x4 Bayer matrix, thresholds 0-15 for ordered dithering
+pub(super) const BAYER_4X4: [[u8; 4]; 4] =
+    [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// True when the image has more than 8 bits per channel (16-bit or float)
+pub fn is_high_depth(img: &DynamicImage) -> bool {
+    let color = img.color();
+    color.bytes_per_pixel() > color.channel_count()
+}
+
+// Reduce 16-bit / float images to 8 bits per channel with ordered dithering
+// Keeps the channel layout (gray / gray+alpha / rgb / rgba)
+// Returns None when the image is already 8-bit
+pub fn reduce_to_8bit(img: &DynamicImage) -> Option<DynamicImage> {
+    if !is_high_depth(img) {
+        return None;
+    }
+
+    let (width, height) = (img.width(), img.height());
+    let color = img.color();
+
+    let reduced = match (color.has_color(), color.has_alpha()) {
+        (false, false) => {
+            let src = img.to_luma32f();
+            DynamicImage::ImageLuma8(image::GrayImage::from_fn(width, height, |x, y| {
+                image::Luma([dither(src.get_pixel(x, y).0[0], x, y)])
+            }))
+        }
+        (false, true) => {
+            let src = img.to_luma_alpha32f();
+            DynamicImage::ImageLumaA8(image::GrayAlphaImage::from_fn(width, height, |x, y| {
+                image::LumaA(src.get_pixel(x, y).0.map(|v| dither(v, x, y)))
+            }))
+        }
+        (true, false) => {
+            let src = img.to_rgb32f();
+            DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| {
+                image::Rgb(src.get_pixel(x, y).0.map(|v| dither(v, x, y)))
+            }))
+        }
+        (true, true) => {
+            let src = img.to_rgba32f();
+            DynamicImage::ImageRgba8(image::RgbaImage::from_fn(width, height, |x, y| {
+                image::Rgba(src.get_pixel(x, y).0.map(|v| dither(v, x, y)))
+            }))
+        }
+    };
+
+    Some(reduced)
+}
+
+// RGBA8 pixels for 8-bit only encoders (dithered when the source is deeper)
+pub fn to_rgba8_dithered(img: &DynamicImage) -> image::RgbaImage {
+    match reduce_to_8bit(img) {
+        Some(reduced) => reduced.to_rgba8(),
+        None => img.to_rgba8(),
+    }
+}
+
+// RGB8 pixels for 8-bit only encoders (dithered when the source is deeper)
+pub fn to_rgb8_dithered(img: &DynamicImage) -> image::RgbImage {
+    match reduce_to_8bit(img) {
+        Some(reduced) => reduced.to_rgb8(),
+        None => img.to_rgb8(),
+    }
+}
+
+// True for float images, which hold linear light (HDR / OpenEXR / float TIFF)
+pub fn is_float(img: &DynamicImage) -> bool {
+    matches!(
+        img,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    )
+}
+
+// Tone map a linear float image to display-referred 16-bit sRGB
+// Light above 1.0 is compressed with extended Reinhard on luminance (brightest visible pixel
+// = white, hue kept), images within 0.0 - 1.0 only get the sRGB curve
+// Returns None for integer images
+pub fn tone_map(img: &DynamicImage) -> Option<DynamicImage> {
+    if !is_float(img) {
+        return None;
+    }
+
+    let src = img.to_rgba32f();
+    let luminance = |[r, g, b, _]: [f32; 4]| 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    // Hidden (fully transparent) pixels do not set the white point
+    let white = src
+        .pixels()
+        .filter(|pixel| pixel.0[3] > 0.0)
+        .map(|pixel| luminance(pixel.0))
+        .filter(|value| value.is_finite())
+        .fold(0.0f32, f32::max);
+
+    let map = |pixel: [f32; 4]| {
+        let l = luminance(pixel);
+        let scale = if white > 1.0 && l > 0.0 {
+            (1.0 + l / (white * white)) / (1.0 + l)
+        } else {
+            1.0
+        };
+        let encode = |v: f32| (linear_to_srgb(v * scale).clamp(0.0, 1.0) * 65535.0).round() as u16;
+        [
+            encode(pixel[0]),
+            encode(pixel[1]),
+            encode(pixel[2]),
+            (pixel[3].clamp(0.0, 1.0) * 65535.0).round() as u16,
+        ]
+    };
+
+    let (width, height) = src.dimensions();
+    Some(if img.has_alpha() {
+        DynamicImage::ImageRgba16(Rgba16Image::from_fn(width, height, |x, y| {
+            Rgba(map(src.get_pixel(x, y).0))
+        }))
+    } else {
+        DynamicImage::ImageRgb16(Rgb16Image::from_fn(width, height, |x, y| {
+            let [r, g, b, _] = map(src.get_pixel(x, y).0);
+            Rgb([r, g, b])
+        }))
+    })
+}
+
+// Tone mapped image for targets that cannot store linear float (None when not needed)
+pub fn tone_map_for_target(img: &DynamicImage, target_format: &str) -> Option<DynamicImage> {
+    if matches!(target_format, "hdr" | "exr") {
+        return None;
+    }
+    tone_map(img)
+}
+
+// Integer (sRGB) images to linear float for HDR / OpenEXR output
+// Returns None for float images, they are linear already
+pub fn to_linear(img: &DynamicImage) -> Option<DynamicImage> {
+    if is_float(img) {
+        return None;
+    }
+
+    Some(if img.has_alpha() {
+        let mut rgba = img.to_rgba32f();
+        for pixel in rgba.pixels_mut() {
+            for channel in &mut pixel.0[..3] {
+                *channel = srgb_to_linear(*channel);
+            }
+        }
+        DynamicImage::ImageRgba32F(rgba)
+    } else {
+        let mut rgb = img.to_rgb32f();
+        for pixel in rgb.pixels_mut() {
+            pixel.0 = pixel.0.map(srgb_to_linear);
+        }
+        DynamicImage::ImageRgb32F(rgb)
+    })
+}
+
+// sRGB transfer curve (IEC 61966-2-1)
+pub(crate) fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub(super) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Quantize a 0.0 - 1.0 sample to 8 bits, threshold taken from the Bayer matrix
+fn dither(value: f32, x: u32, y: u32) -> u8 {
+    let threshold = (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0;
+    (value.clamp(0.0, 1.0) * 255.0 + threshold)
+        .floor()
+        .min(255.0) as u8
+}
@@ -0,0 +1,385 @@
+use std::io::Cursor;
+
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream};
+
+use crate::models::{CombineOptions, JpegOptions, TiffOptions};
+
+use super::alpha::flatten_to_rgb;
+use super::depth::tone_map_for_target;
+use super::jpeg::convert_to_jpeg;
+use super::tiff::{tiff_encoder, write_page};
+
+// PDF user space unit is 1/72 inch
+const POINTS_PER_INCH: f32 = 72.0;
+const POINTS_PER_MM: f32 = POINTS_PER_INCH / 25.4;
+
+// Combine images into one PDF, one JPEG-compressed page per image (in order)
+// Pages are decoded and compressed one at a time, only the JPEG data is kept
+pub fn combine_to_pdf(
+    pages: impl IntoIterator<Item = Result<image::DynamicImage, String>>,
+    options: &CombineOptions,
+) -> Result<Vec<u8>, String> {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let jpeg_options = JpegOptions::default();
+
+    let mut kids: Vec<Object> = Vec::new();
+    for page in pages {
+        let img = page?;
+        let (width, height) = (img.width(), img.height());
+
+        // Same preparation as encode_image for a JPEG target: float sources are tone
+        // mapped, transparent ones flattened over the background at their own depth
+        let img = tone_map_for_target(&img, "jpeg").unwrap_or(img);
+        let rgb = if img.has_alpha() {
+            flatten_to_rgb(&img, options.background)
+        } else {
+            img
+        };
+        let jpeg = convert_to_jpeg(&rgb, options.jpeg_quality, &jpeg_options, None)?;
+        let image_id = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => width,
+                "Height" => height,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8,
+                "Filter" => "DCTDecode",
+            },
+            jpeg,
+        ));
+
+        let layout = page_layout(width, height, options)?;
+        let content = Content {
+            operations: page_operations(&layout),
+        };
+        let content_id = doc.add_object(Stream::new(
+            dictionary! {},
+            content
+                .encode()
+                .map_err(|e| format!("PDF encoding failed: {}", e))?,
+        ));
+
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), layout.page.0.into(), layout.page.1.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Im0" => image_id },
+            },
+        });
+        kids.push(page_id.into());
+    }
+
+    if kids.is_empty() {
+        return Err("No images to combine".to_string());
+    }
+
+    let count = kids.len() as i64;
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count,
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    let mut buffer = Vec::new();
+    doc.save_to(&mut buffer)
+        .map_err(|e| format!("PDF encoding failed: {}", e))?;
+    Ok(buffer)
+}
+
+// Combine images into one multi-page TIFF (default TiffOptions: LZW with horizontal predictor)
+// Pages keep their pixel size, options.dpi is stored as resolution
+pub fn combine_to_tiff(
+    pages: impl IntoIterator<Item = Result<image::DynamicImage, String>>,
+    options: &CombineOptions,
+) -> Result<Vec<u8>, String> {
+    let tiff_options = TiffOptions {
+        dpi: options.dpi,
+        ..TiffOptions::default()
+    };
+    let mut buffer = Cursor::new(Vec::new());
+    let mut encoder = tiff_encoder(&mut buffer, &tiff_options)?;
+
+    let mut count = 0;
+    for page in pages {
+        write_page(&mut encoder, &page?, &tiff_options)?;
+        count += 1;
+    }
+
+    if count == 0 {
+        return Err("No images to combine".to_string());
+    }
+
+    Ok(buffer.into_inner())
+}
+
+// Page size and image placement in points
+struct PageLayout {
+    page: (f32, f32),
+    image: (f32, f32, f32, f32), // x, y, width, height (may exceed the box for "cover")
+    clip: (f32, f32, f32, f32),  // Content box inside the margins
+}
+
+fn page_layout(width: u32, height: u32, options: &CombineOptions) -> Result<PageLayout, String> {
+    let margin = options.margin_mm.max(0.0) * POINTS_PER_MM;
+    // Image size when printed at options.dpi
+    let natural = (
+        width as f32 * POINTS_PER_INCH / options.dpi.max(1) as f32,
+        height as f32 * POINTS_PER_INCH / options.dpi.max(1) as f32,
+    );
+
+    let page = match options.page_size.as_str() {
+        // Page wraps the image, fit mode has nothing to do
+        "fit" => (natural.0 + 2.0 * margin, natural.1 + 2.0 * margin),
+        size => {
+            let (short, long) = match size {
+                "a4" => (595.28, 841.89),
+                "a5" => (419.53, 595.28),
+                "letter" => (612.0, 792.0),
+                "legal" => (612.0, 1008.0),
+                other => return Err(format!("Unsupported page size: {}", other)),
+            };
+            let landscape = match options.orientation.as_str() {
+                "auto" => width > height,
+                "portrait" => false,
+                "landscape" => true,
+                other => return Err(format!("Unsupported orientation: {}", other)),
+            };
+            if landscape {
+                (long, short)
+            } else {
+                (short, long)
+            }
+        }
+    };
+
+    let clip = (margin, margin, page.0 - 2.0 * margin, page.1 - 2.0 * margin);
+    if clip.2 <= 0.0 || clip.3 <= 0.0 {
+        return Err("Margins leave no room for the image".to_string());
+    }
+
+    let scale_x = clip.2 / width as f32;
+    let scale_y = clip.3 / height as f32;
+    let (image_width, image_height) = match options.fit.as_str() {
+        // Whole image visible, centered
+        "contain" => {
+            let scale = scale_x.min(scale_y);
+            (width as f32 * scale, height as f32 * scale)
+        }
+        // Box filled, overflow cropped
+        "cover" => {
+            let scale = scale_x.max(scale_y);
+            (width as f32 * scale, height as f32 * scale)
+        }
+        "stretch" => (clip.2, clip.3),
+        other => return Err(format!("Unsupported fit: {}", other)),
+    };
+
+    Ok(PageLayout {
+        page,
+        image: (
+            clip.0 + (clip.2 - image_width) / 2.0,
+            clip.1 + (clip.3 - image_height) / 2.0,
+            image_width,
+            image_height,
+        ),
+        clip,
+    })
+}
+
+// Clip to the content box, then draw the image XObject scaled into place
+fn page_operations(layout: &PageLayout) -> Vec<Operation> {
+    let (clip_x, clip_y, clip_width, clip_height) = layout.clip;
+    let (x, y, width, height) = layout.image;
+
+    vec![
+        Operation::new("q", vec![]),
+        Operation::new(
+            "re",
+            vec![
+                clip_x.into(),
+                clip_y.into(),
+                clip_width.into(),
+                clip_height.into(),
+            ],
+        ),
+        Operation::new("W", vec![]),
+        Operation::new("n", vec![]),
+        Operation::new(
+            "cm",
+            vec![
+                width.into(),
+                0.into(),
+                0.into(),
+                height.into(),
+                x.into(),
+                y.into(),
+            ],
+        ),
+        Operation::new("Do", vec!["Im0".into()]),
+        Operation::new("Q", vec![]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(page_size: &str, orientation: &str, fit: &str, margin_mm: f32) -> CombineOptions {
+        CombineOptions {
+            page_size: page_size.to_string(),
+            orientation: orientation.to_string(),
+            fit: fit.to_string(),
+            margin_mm,
+            dpi: 72,
+            ..CombineOptions::default()
+        }
+    }
+
+    fn assert_near(actual: (f32, f32, f32, f32), expected: (f32, f32, f32, f32)) {
+        let pairs = [
+            (actual.0, expected.0),
+            (actual.1, expected.1),
+            (actual.2, expected.2),
+            (actual.3, expected.3),
+        ];
+        assert!(
+            pairs.iter().all(|(a, e)| (a - e).abs() < 0.01),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn fit_page_wraps_image_and_margins() {
+        let layout = page_layout(144, 72, &options("fit", "auto", "contain", 0.0)).unwrap();
+        assert_eq!(layout.page, (144.0, 72.0));
+        assert_near(layout.image, (0.0, 0.0, 144.0, 72.0));
+
+        let margin = 10.0 * POINTS_PER_MM;
+        let layout = page_layout(144, 72, &options("fit", "auto", "contain", 10.0)).unwrap();
+        assert_near(
+            (layout.page.0, layout.page.1, 0.0, 0.0),
+            (144.0 + 2.0 * margin, 72.0 + 2.0 * margin, 0.0, 0.0),
+        );
+        assert_near(layout.image, (margin, margin, 144.0, 72.0));
+        assert_near(layout.clip, layout.image);
+    }
+
+    #[test]
+    fn auto_orientation_follows_image() {
+        let wide = page_layout(300, 200, &options("a4", "auto", "contain", 0.0)).unwrap();
+        assert_eq!(wide.page, (841.89, 595.28));
+        let square = page_layout(200, 200, &options("a4", "auto", "contain", 0.0)).unwrap();
+        assert_eq!(square.page, (595.28, 841.89));
+        let forced = page_layout(300, 200, &options("letter", "portrait", "contain", 0.0)).unwrap();
+        assert_eq!(forced.page, (612.0, 792.0));
+    }
+
+    #[test]
+    fn fit_modes_place_image() {
+        // 2:1 image on a portrait A4 page
+        let contain = page_layout(200, 100, &options("a4", "portrait", "contain", 0.0)).unwrap();
+        assert_near(
+            contain.image,
+            (0.0, (841.89 - 297.64) / 2.0, 595.28, 297.64),
+        );
+
+        let cover = page_layout(200, 100, &options("a4", "portrait", "cover", 0.0)).unwrap();
+        assert_near(
+            cover.image,
+            ((595.28 - 1683.78) / 2.0, 0.0, 1683.78, 841.89),
+        );
+        assert_near(cover.clip, (0.0, 0.0, 595.28, 841.89));
+
+        let stretch = page_layout(200, 100, &options("a4", "portrait", "stretch", 5.0)).unwrap();
+        assert_near(stretch.image, stretch.clip);
+    }
+
+    #[test]
+    fn single_pixel_image_is_scaled_up() {
+        let layout = page_layout(1, 1, &options("letter", "auto", "contain", 0.0)).unwrap();
+        assert_near(layout.image, (0.0, 90.0, 612.0, 612.0));
+
+        // dpi 0 is treated as 1
+        let layout = page_layout(
+            1,
+            1,
+            &CombineOptions {
+                dpi: 0,
+                ..options("fit", "auto", "contain", 0.0)
+            },
+        )
+        .unwrap();
+        assert_eq!(layout.page, (72.0, 72.0));
+    }
+
+    #[test]
+    fn rejects_impossible_layouts() {
+        assert!(page_layout(10, 10, &options("a4", "auto", "contain", 200.0)).is_err());
+        assert!(page_layout(10, 10, &options("a3", "auto", "contain", 0.0)).is_err());
+        assert!(page_layout(10, 10, &options("a4", "sideways", "contain", 0.0)).is_err());
+        assert!(page_layout(10, 10, &options("a4", "auto", "tile", 0.0)).is_err());
+    }
+
+    #[test]
+    fn pages_are_prepared_like_jpeg_conversions() {
+        // Linear float 0.2 is sRGB 124 once tone mapped (51 when merely truncated)
+        let float = image::DynamicImage::ImageRgb32F(image::Rgb32FImage::from_pixel(
+            8,
+            8,
+            image::Rgb([0.2; 3]),
+        ));
+        // Fully transparent 16-bit page shows only the background
+        let transparent = image::DynamicImage::ImageRgba16(image::ImageBuffer::from_pixel(
+            8,
+            8,
+            image::Rgba([0; 4]),
+        ));
+        let options = CombineOptions {
+            background: [0, 128, 255],
+            jpeg_quality: 100,
+            ..CombineOptions::default()
+        };
+        let pdf = combine_to_pdf([Ok(float), Ok(transparent)], &options).unwrap();
+
+        let doc = Document::load_mem(&pdf).unwrap();
+        let mut pixels: Vec<[u8; 3]> = doc
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .filter(|stream| stream.dict.get(b"Filter").ok() == Some(&"DCTDecode".into()))
+            .map(|stream| {
+                image::load_from_memory(&stream.content)
+                    .unwrap()
+                    .to_rgb8()
+                    .get_pixel(4, 4)
+                    .0
+            })
+            .collect();
+        pixels.sort();
+        assert_eq!(pixels.len(), 2);
+
+        let near = |actual: [u8; 3], expected: [u8; 3]| {
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(&a, e)| a.abs_diff(e) <= 3)
+        };
+        assert!(near(pixels[0], [0, 128, 255]), "{:?}", pixels[0]);
+        assert!(near(pixels[1], [124, 124, 124]), "{:?}", pixels[1]);
+    }
+}
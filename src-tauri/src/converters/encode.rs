@@ -0,0 +1,231 @@
+use crate::animation::Animation;
+use crate::models::ConversionOptions;
+
+use super::alpha::{flatten_alpha, flatten_for_target};
+use super::basic::{convert_to_bmp, convert_to_pnm, convert_to_qoi, convert_to_tga};
+use super::depth::tone_map_for_target;
+use super::gif::{convert_animation_to_gif, convert_to_gif};
+use super::hdr::{convert_to_exr, convert_to_hdr};
+use super::icon::{convert_to_icns, convert_to_ico};
+use super::jxl::transcode_jpeg_to_jxl;
+use super::perceptual::encode_to_target_dssim;
+use super::png::convert_animation_to_png;
+use super::target_size::encode_to_target_size;
+use super::tiff::convert_to_tiff;
+use super::webp::convert_animation_to_webp;
+use super::{convert_to_avif, convert_to_jpeg, convert_to_jxl, convert_to_png, convert_to_webp};
+
+// Encoded output with the settings that were actually applied
+pub struct EncodedImage {
+    pub data: Vec<u8>,
+    pub quality: Option<u8>,            // Quality chosen in target size mode
+    pub resized_to: Option<(u32, u32)>, // Dimensions when downscaled to reach target size
+    pub dssim: Option<f64>,             // Achieved DSSIM in perceptual target mode
+}
+
+// Lossy formats whose quality can be searched (target size / perceptual modes)
+pub fn supports_quality_search(options: &ConversionOptions) -> bool {
+    match options.target_format.as_str() {
+        "jpeg" | "jpg" | "avif" => true,
+        "webp" => !options.webp.is_lossless(),
+        "jxl" => options.jxl.mode == "lossy",
+        _ => false,
+    }
+}
+
+// Encode image into target format described by conversion options
+pub fn encode_image(
+    img: &image::DynamicImage,
+    options: &ConversionOptions,
+    exif_bytes: Option<&[u8]>,
+) -> Result<EncodedImage, String> {
+    // Float (linear HDR) sources are tone mapped for every target but HDR / OpenEXR
+    let tone_mapped = tone_map_for_target(img, &options.target_format);
+    let img = tone_mapped.as_ref().unwrap_or(img);
+
+    // Composite transparent pixels over background before any encoding / scoring
+    let flattened = flatten_for_target(img, options);
+    let img = flattened.as_ref().unwrap_or(img);
+
+    match (options.max_file_size, options.max_dssim) {
+        (Some(_), Some(_)) => {
+            Err("Target file size and perceptual target cannot be combined".to_string())
+        }
+        // Target file size mode: search quality instead of using it directly
+        (Some(max_size), None) => encode_to_target_size(img, options, max_size, exif_bytes),
+        // Perceptual mode: lowest quality meeting the DSSIM threshold
+        (None, Some(max_dssim)) => encode_to_target_dssim(img, options, max_dssim, exif_bytes),
+        (None, None) => Ok(EncodedImage {
+            data: encode_with_quality(img, options, options.quality, exif_bytes)?,
+            quality: None,
+            resized_to: None,
+            dssim: None,
+        }),
+    }
+}
+
+// Encode a still image like encode_image, reusing the encoded source when the target can
+// (JPEG sources to JPEG XL "jpeg" mode are recompressed losslessly instead of re-encoded)
+pub fn encode_source(
+    source: &[u8],
+    img: &image::DynamicImage,
+    options: &ConversionOptions,
+    exif_bytes: Option<&[u8]>,
+) -> Result<EncodedImage, String> {
+    if options.target_format == "jxl"
+        && options.jxl.mode == "jpeg"
+        && image::guess_format(source).ok() == Some(image::ImageFormat::Jpeg)
+    {
+        // EXIF is part of the reconstruction data, exif_bytes would duplicate it
+        // (and is stripped from the source when it should not be kept)
+        return Ok(EncodedImage {
+            data: transcode_jpeg_to_jxl(source, &options.jxl, options.preserve_exif)?,
+            quality: None,
+            resized_to: None,
+            dssim: None,
+        });
+    }
+
+    encode_image(img, options, exif_bytes)
+}
+
+// Target formats that can store every frame of an animation
+pub fn supports_animation(target_format: &str) -> bool {
+    matches!(target_format, "webp" | "png" | "gif")
+}
+
+// Encode all frames of an animation to an animated target format
+pub fn encode_animation(
+    animation: &Animation,
+    options: &ConversionOptions,
+    exif_bytes: Option<&[u8]>,
+) -> Result<EncodedImage, String> {
+    if options.max_file_size.is_some() || options.max_dssim.is_some() {
+        // Use first_frame_only to convert animated sources in these modes
+        return Err(
+            "Target size and perceptual modes are not supported for animations".to_string(),
+        );
+    }
+
+    // Flatten each frame over the background when requested (animated targets keep alpha)
+    let flattened;
+    let animation = if options.flatten_alpha {
+        let mut copy = animation.clone();
+        for frame in &mut copy.frames {
+            let rgb = flatten_alpha(
+                &image::DynamicImage::ImageRgba8(std::mem::take(&mut frame.image)),
+                options.background,
+            );
+            frame.image = image::DynamicImage::ImageRgb8(rgb).to_rgba8();
+        }
+        flattened = copy;
+        &flattened
+    } else {
+        animation
+    };
+
+    let data = match options.target_format.as_str() {
+        "webp" => convert_animation_to_webp(animation, options.quality, &options.webp, exif_bytes),
+        "png" => convert_animation_to_png(animation, options.quality, &options.png, exif_bytes),
+        "gif" => convert_animation_to_gif(animation, &options.gif),
+        target_format => Err(format!(
+            "Animated output is not supported for {}",
+            target_format
+        )),
+    }?;
+
+    Ok(EncodedImage {
+        data,
+        quality: None,
+        resized_to: None,
+        dssim: None,
+    })
+}
+
+// Encode with an explicit quality (overrides options.quality)
+pub fn encode_with_quality(
+    img: &image::DynamicImage,
+    options: &ConversionOptions,
+    quality: u8,
+    exif_bytes: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    match options.target_format.as_str() {
+        // Dev mode: intentional error for testing
+        "error" => Err("Intentional error for testing (dev mode)".to_string()),
+        "webp" => convert_to_webp(img, quality, &options.webp, exif_bytes),
+        "jpeg" | "jpg" => convert_to_jpeg(img, quality, &options.jpeg, exif_bytes),
+        "png" => convert_to_png(img, quality, &options.png, exif_bytes),
+        "avif" => convert_to_avif(img, quality, options.avif_speed, &options.avif, exif_bytes),
+        "gif" => convert_to_gif(img, &options.gif),
+        "jxl" => convert_to_jxl(img, quality, &options.jxl, exif_bytes),
+        "ico" => convert_to_ico(img, quality, &options.icon),
+        "icns" => convert_to_icns(img, quality, &options.icon),
+        // TIFF: no EXIF preservation
+        "tiff" | "tif" => convert_to_tiff(img, &options.tiff),
+        "bmp" => convert_to_bmp(img),
+        "tga" => convert_to_tga(img),
+        "qoi" => convert_to_qoi(img),
+        "pnm" => convert_to_pnm(img),
+        "hdr" => convert_to_hdr(img),
+        "exr" => convert_to_exr(img),
+        target_format => Err(format!("Unsupported format: {}", target_format)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::test_util;
+    use crate::decode::decode_source;
+    use crate::exif::extract_exif_raw_bytes;
+    use crate::models::{JpegOptions, JxlOptions, RawOptions};
+
+    // Little endian TIFF header with a single Orientation entry
+    const EXIF: [u8; 26] = [
+        b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    fn options(preserve_exif: bool) -> ConversionOptions {
+        ConversionOptions {
+            preserve_exif,
+            jxl: JxlOptions {
+                mode: "jpeg".to_string(),
+                effort: 1,
+            },
+            ..test_util::options("jxl")
+        }
+    }
+
+    #[test]
+    fn jpeg_recompression_keeps_exif_only_when_preserved() {
+        let img = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 16) as u8, 64])
+        }));
+        let source = convert_to_jpeg(&img, 90, &JpegOptions::default(), Some(&EXIF)).unwrap();
+        assert_eq!(extract_exif_raw_bytes(&source).unwrap(), EXIF);
+
+        // libjxl may store the box brotli compressed, its original type stays readable
+        let kept = encode_source(&source, &img, &options(true), Some(&EXIF)).unwrap();
+        assert!(kept.data.windows(4).any(|w| w == b"Exif"));
+
+        let stripped = encode_source(&source, &img, &options(false), None).unwrap();
+        assert!(!stripped.data.windows(4).any(|w| w == b"Exif"));
+        assert_eq!(extract_exif_raw_bytes(&stripped.data), None);
+    }
+
+    #[test]
+    fn jxl_decodes_at_source_depth() {
+        let img = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 16) as u8, 64])
+        }));
+        let deep = image::DynamicImage::ImageRgb16(img.to_rgb16());
+
+        // Lossless (no JPEG source to recompress), so pixels must come back exactly
+        for source in [img.clone(), deep] {
+            let encoded = encode_image(&source, &options(false), None).unwrap();
+            let decoded = decode_source(&encoded.data, &RawOptions::default()).unwrap();
+            assert_eq!(decoded.color(), source.color());
+            assert_eq!(decoded.to_rgb8(), img.to_rgb8());
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::models::{FaviconOptions, IconOptions, PngOptions};
+
+use super::icon::convert_to_ico;
+use super::png::convert_to_png;
+use super::resize::fit_square;
+
+// Web icon set: (file name, contents) for favicon.ico, the PNG icons and site.webmanifest
+pub fn favicon_set(
+    img: &image::DynamicImage,
+    name: &str,
+    options: &FaviconOptions,
+) -> Result<Vec<(&'static str, Vec<u8>)>, String> {
+    let png_options = PngOptions::default();
+    let png = |icon: image::RgbaImage| {
+        convert_to_png(
+            &image::DynamicImage::ImageRgba8(icon),
+            9,
+            &png_options,
+            None,
+        )
+    };
+
+    let ico_options = IconOptions {
+        sizes: vec![16, 32, 48],
+        ..IconOptions::default()
+    };
+
+    Ok(vec![
+        ("favicon.ico", convert_to_ico(img, 9, &ico_options)?),
+        // iOS renders transparency as black, flatten over the background color
+        (
+            "apple-touch-icon.png",
+            png(fit_square(img, 180, 0.0, Some(options.background_color)))?,
+        ),
+        ("icon-192.png", png(fit_square(img, 192, 0.0, None))?),
+        ("icon-512.png", png(fit_square(img, 512, 0.0, None))?),
+        // Launchers crop maskable icons, keep the content inside the safe zone
+        (
+            "icon-maskable-512.png",
+            png(fit_square(
+                img,
+                512,
+                options.maskable_padding,
+                Some(options.background_color),
+            ))?,
+        ),
+        ("site.webmanifest", web_manifest(name, options)?),
+    ])
+}
+
+// Web app manifest referencing the PNG icons (name falls back to the source file stem)
+fn web_manifest(name: &str, options: &FaviconOptions) -> Result<Vec<u8>, String> {
+    let name = if options.name.is_empty() {
+        name
+    } else {
+        &options.name
+    };
+    let short_name = if options.short_name.is_empty() {
+        name
+    } else {
+        &options.short_name
+    };
+
+    let manifest = serde_json::json!({
+        "name": name,
+        "short_name": short_name,
+        "icons": [
+            { "src": "/icon-192.png", "sizes": "192x192", "type": "image/png" },
+            { "src": "/icon-512.png", "sizes": "512x512", "type": "image/png" },
+            {
+                "src": "/icon-maskable-512.png",
+                "sizes": "512x512",
+                "type": "image/png",
+                "purpose": "maskable"
+            }
+        ],
+        "theme_color": hex_color(options.theme_color),
+        "background_color": hex_color(options.background_color),
+        "display": "standalone"
+    });
+
+    serde_json::to_vec_pretty(&manifest).map_err(|e| format!("Failed to write manifest: {}", e))
+}
+
+fn hex_color([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::test_util::noise;
+
+    #[test]
+    fn favicon_set_has_every_icon_size() {
+        let files = favicon_set(&noise(300, 200), "logo", &FaviconOptions::default()).unwrap();
+        let names: Vec<&str> = files.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "favicon.ico",
+                "apple-touch-icon.png",
+                "icon-192.png",
+                "icon-512.png",
+                "icon-maskable-512.png",
+                "site.webmanifest"
+            ]
+        );
+
+        // ICO directory: 6 byte header, then one 16 byte entry per size
+        let ico = &files[0].1;
+        let count = u16::from_le_bytes([ico[4], ico[5]]) as usize;
+        let sizes: Vec<(u8, u8)> = (0..count)
+            .map(|i| (ico[6 + i * 16], ico[7 + i * 16]))
+            .collect();
+        assert_eq!(sizes, [(16, 16), (32, 32), (48, 48)]);
+
+        for ((name, data), size) in files[1..5].iter().zip([180, 192, 512, 512]) {
+            let png = image::load_from_memory(data).unwrap();
+            assert_eq!((png.width(), png.height()), (size, size), "{}", name);
+        }
+
+        let manifest: serde_json::Value = serde_json::from_slice(&files[5].1).unwrap();
+        assert_eq!(manifest["name"], "logo");
+        assert_eq!(manifest["icons"].as_array().unwrap().len(), 3);
+    }
+}
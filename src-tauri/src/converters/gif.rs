@@ -0,0 +1,91 @@
+use std::borrow::Cow;
+
+use gif::{DisposalMethod, Encoder, Frame, Repeat};
+use image::RgbaImage;
+
+use crate::animation::Animation;
+use crate::models::GifOptions;
+
+use super::depth::to_rgba8_dithered;
+use super::quantize::{quantize, Dithering, Transparency};
+
+// Convert image to GIF with a quantized palette
+pub fn convert_to_gif(img: &image::DynamicImage, options: &GifOptions) -> Result<Vec<u8>, String> {
+    let rgba = to_rgba8_dithered(img);
+    encode_gif(rgba.dimensions(), [(&rgba, 0)], None, options)
+}
+
+// Convert every frame to an animated GIF (each frame quantized to its own palette)
+pub fn convert_animation_to_gif(
+    animation: &Animation,
+    options: &GifOptions,
+) -> Result<Vec<u8>, String> {
+    // plays = 1 means no NETSCAPE loop extension, n > 1 repeats n - 1 times
+    let repeat = match animation.plays {
+        0 => Some(Repeat::Infinite),
+        1 => None,
+        plays => Some(Repeat::Finite((plays - 1).min(u16::MAX as u32) as u16)),
+    };
+
+    let frames = animation
+        .frames
+        .iter()
+        .map(|frame| (&frame.image, frame.delay_ms));
+    encode_gif(animation.dimensions(), frames, repeat, options)
+}
+
+// Quantize and write full canvas frames (delay in milliseconds)
+fn encode_gif<'a>(
+    (width, height): (u32, u32),
+    frames: impl IntoIterator<Item = (&'a RgbaImage, u32)>,
+    repeat: Option<Repeat>,
+    options: &GifOptions,
+) -> Result<Vec<u8>, String> {
+    let dithering = Dithering::parse(&options.dithering)?;
+    let width = u16::try_from(width).map_err(|_| "GIF width exceeds 65535".to_string())?;
+    let height = u16::try_from(height).map_err(|_| "GIF height exceeds 65535".to_string())?;
+
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buffer, width, height, &[])
+            .map_err(|e| format!("GIF encoding failed: {}", e))?;
+        if let Some(repeat) = repeat {
+            encoder
+                .set_repeat(repeat)
+                .map_err(|e| format!("GIF encoding failed: {}", e))?;
+        }
+
+        for (image, delay_ms) in frames {
+            let quantized = quantize(
+                image,
+                options.colors as usize,
+                dithering,
+                1.0,
+                Transparency::Binary(options.alpha_threshold),
+            );
+            let palette: Vec<u8> = quantized
+                .palette
+                .iter()
+                .flat_map(|color| [color[0], color[1], color[2]])
+                .collect();
+
+            let frame = Frame {
+                width,
+                height,
+                // GIF delays are in 1/100 s
+                delay: ((delay_ms + 5) / 10).min(u16::MAX as u32) as u16,
+                // Frames are full canvases, clear so transparent areas don't show the previous one
+                dispose: DisposalMethod::Background,
+                transparent: quantized.transparent,
+                palette: Some(palette),
+                buffer: Cow::Owned(quantized.indices),
+                ..Frame::default()
+            };
+            encoder
+                .write_frame(&frame)
+                .map_err(|e| format!("GIF encoding failed: {}", e))?;
+        }
+    }
+
+    Ok(buffer)
+}
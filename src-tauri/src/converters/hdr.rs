@@ -0,0 +1,25 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat};
+
+use super::depth::to_linear;
+
+// Radiance HDR: linear RGB float stored as RGBE (no alpha, flattened by encode_image)
+pub fn convert_to_hdr(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let linear = to_linear(img);
+    let rgb = DynamicImage::ImageRgb32F(linear.as_ref().unwrap_or(img).to_rgb32f());
+    write_float(&rgb, ImageFormat::Hdr)
+}
+
+// OpenEXR: linear RGB / RGBA float
+pub fn convert_to_exr(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let linear = to_linear(img);
+    write_float(linear.as_ref().unwrap_or(img), ImageFormat::OpenExr)
+}
+
+fn write_float(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    img.write_to(&mut Cursor::new(&mut buffer), format)
+        .map_err(|e| format!("{:?} encoding failed: {}", format, e))?;
+    Ok(buffer)
+}
@@ -0,0 +1,326 @@
+use image::RgbaImage;
+
+use crate::models::{IconOptions, PngOptions};
+
+use super::png::convert_to_png;
+use super::resize::fit_square;
+
+// Sizes used when IconOptions.sizes is empty
+const ICO_DEFAULT_SIZES: [u32; 7] = [16, 24, 32, 48, 64, 128, 256];
+const ICNS_DEFAULT_SIZES: [u32; 7] = [16, 32, 64, 128, 256, 512, 1024];
+
+// ICNS element types per pixel size: 1x type and the 2x (retina) type of half the size
+const ICNS_TYPES: [(u32, [u8; 4], Option<[u8; 4]>); 7] = [
+    (16, *b"icp4", None),
+    (32, *b"icp5", Some(*b"ic11")),
+    (64, *b"icp6", Some(*b"ic12")),
+    (128, *b"ic07", None),
+    (256, *b"ic08", Some(*b"ic13")),
+    (512, *b"ic09", Some(*b"ic14")),
+    (1024, *b"ic10", None),
+];
+
+// Windows icon: BMP entries below png_min_size, PNG entries from there (256 at most)
+pub fn convert_to_ico(
+    img: &image::DynamicImage,
+    compression: u8,
+    options: &IconOptions,
+) -> Result<Vec<u8>, String> {
+    let sizes = icon_sizes(&options.sizes, &ICO_DEFAULT_SIZES)?;
+    if let Some(size) = sizes.iter().find(|&&size| size > 256) {
+        return Err(format!("ICO sizes are limited to 256 ({} requested)", size));
+    }
+
+    let mut entries = Vec::with_capacity(sizes.len());
+    for &size in &sizes {
+        let icon = fit_square(img, size, 0.0, None);
+        let data = if size >= options.png_min_size {
+            icon_png(icon, compression)?
+        } else {
+            ico_bitmap(&icon)
+        };
+        entries.push((size, data));
+    }
+
+    // ICONDIR header, then one 16 byte ICONDIRENTRY per image, then the image data
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(&0u16.to_le_bytes());
+    buffer.extend_from_slice(&1u16.to_le_bytes()); // 1 = icon
+    buffer.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+
+    let mut offset = 6 + 16 * entries.len();
+    for (size, data) in &entries {
+        // 0 means 256 in the one byte width / height fields
+        let dimension = if *size >= 256 { 0 } else { *size as u8 };
+        buffer.extend_from_slice(&[dimension, dimension, 0, 0]);
+        buffer.extend_from_slice(&1u16.to_le_bytes()); // Color planes
+        buffer.extend_from_slice(&32u16.to_le_bytes()); // Bits per pixel
+        buffer.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += data.len();
+    }
+    for (_, data) in entries {
+        buffer.extend_from_slice(&data);
+    }
+
+    Ok(buffer)
+}
+
+// macOS icon: every entry is PNG (supported for all types since OS X 10.7)
+pub fn convert_to_icns(
+    img: &image::DynamicImage,
+    compression: u8,
+    options: &IconOptions,
+) -> Result<Vec<u8>, String> {
+    let sizes = icon_sizes(&options.sizes, &ICNS_DEFAULT_SIZES)?;
+
+    let mut elements: Vec<([u8; 4], Vec<u8>)> = Vec::new();
+    for &size in &sizes {
+        let (_, icon_type, retina_type) = ICNS_TYPES
+            .iter()
+            .find(|(type_size, _, _)| *type_size == size)
+            .ok_or_else(|| {
+                format!(
+                    "Unsupported ICNS size: {} (16, 32, 64, 128, 256, 512 or 1024)",
+                    size
+                )
+            })?;
+
+        let data = icon_png(fit_square(img, size, 0.0, None), compression)?;
+        if let Some(retina_type) = retina_type {
+            elements.push((*retina_type, data.clone()));
+        }
+        elements.push((*icon_type, data));
+    }
+
+    // Big-endian lengths include the 8 byte headers
+    let total: usize = 8 + elements
+        .iter()
+        .map(|(_, data)| 8 + data.len())
+        .sum::<usize>();
+    let total = u32::try_from(total).map_err(|_| "ICNS file too large".to_string())?;
+
+    let mut buffer = Vec::with_capacity(total as usize);
+    buffer.extend_from_slice(b"icns");
+    buffer.extend_from_slice(&total.to_be_bytes());
+    for (icon_type, data) in elements {
+        buffer.extend_from_slice(&icon_type);
+        buffer.extend_from_slice(&((8 + data.len()) as u32).to_be_bytes());
+        buffer.extend_from_slice(&data);
+    }
+
+    Ok(buffer)
+}
+
+// Icon entries are always 8-bit RGBA PNGs, whatever the batch's PNG settings
+// (some icon readers reject palette or grayscale entries)
+fn icon_png(icon: RgbaImage, compression: u8) -> Result<Vec<u8>, String> {
+    let options = PngOptions {
+        reduce_color: false,
+        quantize: false,
+        ..PngOptions::default()
+    };
+    convert_to_png(
+        &image::DynamicImage::ImageRgba8(icon),
+        compression,
+        &options,
+        None,
+    )
+}
+
+// Requested sizes (or the defaults), sorted and deduplicated, each 16-1024
+fn icon_sizes(requested: &[u32], defaults: &[u32]) -> Result<Vec<u32>, String> {
+    let mut sizes = if requested.is_empty() {
+        defaults.to_vec()
+    } else {
+        requested.to_vec()
+    };
+    if let Some(size) = sizes.iter().find(|size| !(16..=1024).contains(*size)) {
+        return Err(format!("Icon size must be between 16 and 1024 ({})", size));
+    }
+
+    sizes.sort_unstable();
+    sizes.dedup();
+    Ok(sizes)
+}
+
+// 32-bit BMP entry: BITMAPINFOHEADER (height doubled for the mask), bottom-up BGRA rows,
+// then the 1-bit AND mask marking fully transparent pixels for legacy renderers
+fn ico_bitmap(icon: &RgbaImage) -> Vec<u8> {
+    let (width, height) = icon.dimensions();
+    // Mask rows are padded to 4 bytes
+    let mask_stride = (width as usize).div_ceil(32) * 4;
+    let pixel_bytes = (width * height * 4) as usize;
+    let mask_bytes = mask_stride * height as usize;
+
+    let mut buffer = Vec::with_capacity(40 + pixel_bytes + mask_bytes);
+    buffer.extend_from_slice(&40u32.to_le_bytes());
+    buffer.extend_from_slice(&(width as i32).to_le_bytes());
+    buffer.extend_from_slice(&(height as i32 * 2).to_le_bytes());
+    buffer.extend_from_slice(&1u16.to_le_bytes()); // Planes
+    buffer.extend_from_slice(&32u16.to_le_bytes()); // Bits per pixel
+    buffer.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+    buffer.extend_from_slice(&((pixel_bytes + mask_bytes) as u32).to_le_bytes());
+    buffer.extend_from_slice(&[0; 16]); // Resolution and palette fields unused
+
+    for row in icon.rows().rev() {
+        for pixel in row {
+            let [r, g, b, a] = pixel.0;
+            buffer.extend_from_slice(&[b, g, r, a]);
+        }
+    }
+
+    for row in icon.rows().rev() {
+        let mut mask = vec![0u8; mask_stride];
+        for (x, pixel) in row.enumerate() {
+            if pixel[3] == 0 {
+                mask[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        buffer.extend_from_slice(&mask);
+    }
+
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(sizes: &[u32], png_min_size: u32) -> IconOptions {
+        IconOptions {
+            sizes: sizes.to_vec(),
+            png_min_size,
+        }
+    }
+
+    fn opaque(width: u32, height: u32) -> image::DynamicImage {
+        image::DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([(x * 7) as u8, (y * 5) as u8, 90, 255])
+        }))
+    }
+
+    // ICONDIR entries as (width byte, data)
+    fn ico_entries(data: &[u8]) -> Vec<(u8, &[u8])> {
+        let count = u16::from_le_bytes([data[4], data[5]]) as usize;
+        (0..count)
+            .map(|i| {
+                let entry = &data[6 + 16 * i..6 + 16 * (i + 1)];
+                let len = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as usize;
+                let offset = u32::from_le_bytes(entry[12..16].try_into().unwrap()) as usize;
+                (entry[0], &data[offset..offset + len])
+            })
+            .collect()
+    }
+
+    // ICNS elements as (type, data without header)
+    fn icns_elements(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        let mut elements = Vec::new();
+        let mut offset = 8;
+        while offset < data.len() {
+            let len = u32::from_be_bytes(data[offset + 4..offset + 8].try_into().unwrap());
+            let end = offset + len as usize;
+            elements.push((
+                data[offset..offset + 4].try_into().unwrap(),
+                &data[offset + 8..end],
+            ));
+            offset = end;
+        }
+        elements
+    }
+
+    #[test]
+    fn ico_mixes_bitmap_and_png_entries() {
+        let data = convert_to_ico(&opaque(40, 20), 6, &options(&[256, 16, 48, 16], 48)).unwrap();
+
+        let entries = ico_entries(&data);
+        assert_eq!(entries.iter().map(|e| e.0).collect::<Vec<_>>(), [16, 48, 0]);
+        // 16: BITMAPINFOHEADER with doubled height, 48 and 256: PNG
+        assert_eq!(&entries[0].1[..4], &40u32.to_le_bytes());
+        assert_eq!(&entries[0].1[8..12], &32i32.to_le_bytes());
+        assert!(entries[1..]
+            .iter()
+            .all(|(_, png)| png.starts_with(b"\x89PNG")));
+
+        let decoded = image::load_from_memory_with_format(&data, image::ImageFormat::Ico).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (256, 256));
+    }
+
+    #[test]
+    fn png_entries_stay_rgba() {
+        // Opaque gray would be reduced to a gray PNG with the default PNG options
+        let gray = image::DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            64,
+            64,
+            image::Rgba([128, 128, 128, 255]),
+        ));
+        let ico = convert_to_ico(&gray, 6, &options(&[64], 32)).unwrap();
+        let icns = convert_to_icns(&gray, 6, &options(&[64], 0)).unwrap();
+
+        let pngs = ico_entries(&ico)
+            .into_iter()
+            .chain(icns_elements(&icns).into_iter().map(|(_, png)| (0, png)));
+        for (_, png) in pngs {
+            // IHDR bit depth and color type
+            assert_eq!(&png[24..26], &[8, 6]);
+        }
+    }
+
+    #[test]
+    fn ico_bitmap_masks_transparent_pixels() {
+        let icon = RgbaImage::from_fn(33, 2, |x, _| {
+            image::Rgba([1, 2, 3, if x == 0 { 0 } else { 255 }])
+        });
+        let bitmap = ico_bitmap(&icon);
+        // Mask rows of 33 pixels are padded to 8 bytes
+        let mask = &bitmap[40 + 33 * 2 * 4..];
+        assert_eq!(mask.len(), 2 * 8);
+        assert!(mask.chunks(8).all(|row| row == [0x80, 0, 0, 0, 0, 0, 0, 0]));
+        // Bottom-up BGRA
+        assert_eq!(&bitmap[40..44], &[3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn fully_transparent_and_single_pixel_sources() {
+        let transparent = image::DynamicImage::ImageRgba8(RgbaImage::new(10, 10));
+        let data = convert_to_ico(&transparent, 6, &options(&[16], 256)).unwrap();
+        let bitmap = ico_entries(&data)[0].1;
+        assert!(bitmap[40 + 16 * 16 * 4..]
+            .chunks(4)
+            .all(|row| row == [0xFF, 0xFF, 0, 0]));
+
+        for data in [
+            convert_to_ico(&opaque(1, 1), 6, &options(&[], 64)),
+            convert_to_icns(&opaque(1, 1), 6, &options(&[], 0)),
+        ] {
+            assert!(!data.unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn icns_writes_retina_types() {
+        let data = convert_to_icns(&opaque(20, 30), 6, &options(&[32, 16], 0)).unwrap();
+        assert_eq!(&data[..4], b"icns");
+        assert_eq!(
+            u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize,
+            data.len()
+        );
+
+        let elements = icns_elements(&data);
+        let types: Vec<&[u8; 4]> = elements.iter().map(|(t, _)| t).collect();
+        assert_eq!(types, [b"icp4", b"ic11", b"icp5"]);
+        for (icon_type, png) in &elements {
+            let size = if icon_type == b"icp4" { 16 } else { 32 };
+            let icon = image::load_from_memory(png).unwrap();
+            assert_eq!((icon.width(), icon.height()), (size, size));
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_sizes() {
+        let img = opaque(8, 8);
+        assert!(convert_to_ico(&img, 6, &options(&[512], 0)).is_err());
+        assert!(convert_to_ico(&img, 6, &options(&[8], 0)).is_err());
+        assert!(convert_to_icns(&img, 6, &options(&[48], 0)).is_err());
+    }
+}
@@ -1,24 +1,53 @@
 use img_parts::jpeg::Jpeg;
 use img_parts::{Bytes, ImageEXIF};
 
-// Convert to JPEG with optional EXIF preservation
+use crate::models::JpegOptions;
+
+use super::depth::to_rgb8_dithered;
+
+// Convert to JPEG using mozjpeg (progressive, subsampling, trellis) with optional EXIF preservation
 pub fn convert_to_jpeg(
     img: &image::DynamicImage,
     quality: u8,
+    options: &JpegOptions,
     exif_bytes: Option<&[u8]>,
 ) -> Result<Vec<u8>, String> {
-    let mut buffer = Vec::new();
-    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality);
-
-    let rgb_img = img.to_rgb8();
-    encoder
-        .encode(
-            rgb_img.as_raw(),
-            img.width(),
-            img.height(),
-            image::ExtendedColorType::Rgb8,
-        )
-        .map_err(|e| format!("JPEG encoding failed: {}", e))?;
+    let chroma = chroma_pixel_size(&options.chroma_subsampling)?;
+
+    // Transparent images are already flattened over the background by encode_image
+    let rgb_img = to_rgb8_dithered(img);
+    let (width, height) = rgb_img.dimensions();
+
+    // mozjpeg reports errors by unwinding, keep them inside this function
+    let buffer = std::panic::catch_unwind(std::panic::AssertUnwindSafe(
+        || -> std::io::Result<Vec<u8>> {
+            let mut comp = mozjpeg::Compress::new(mozjpeg::ColorSpace::JCS_RGB);
+
+            // Default mozjpeg profile enables trellis quantization,
+            // libjpeg-turbo compatible defaults disable it
+            if !options.trellis {
+                comp.set_fastest_defaults();
+            }
+
+            comp.set_size(width as usize, height as usize);
+            comp.set_quality(quality as f32);
+            comp.set_optimize_coding(options.optimize_huffman);
+            comp.set_chroma_sampling_pixel_sizes(chroma, chroma);
+            if options.progressive {
+                comp.set_progressive_mode();
+                comp.set_use_scans_in_trellis(options.trellis);
+            } else {
+                // The default mozjpeg profile is already progressive, drop its scan script
+                comp.set_optimize_scans(false);
+            }
+
+            let mut started = comp.start_compress(Vec::new())?;
+            started.write_scanlines(rgb_img.as_raw())?;
+            started.finish()
+        },
+    ))
+    .map_err(|_| "JPEG encoding failed: mozjpeg error".to_string())?
+    .map_err(|e| format!("JPEG encoding failed: {}", e))?;
 
     // Insert EXIF data if provided
     if let Some(exif) = exif_bytes {
@@ -32,3 +61,62 @@ pub fn convert_to_jpeg(
 
     Ok(buffer)
 }
+
+// Map subsampling name to chroma "pixel" size per luma pixel
+fn chroma_pixel_size(subsampling: &str) -> Result<(u8, u8), String> {
+    match subsampling {
+        "444" => Ok((1, 1)),
+        "422" => Ok((2, 1)),
+        "420" => Ok((2, 2)),
+        _ => Err(format!("Unsupported chroma subsampling: {}", subsampling)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frame marker (SOF0 = baseline, SOF2 = progressive) found by walking the segments
+    fn frame_marker(data: &[u8]) -> Option<u8> {
+        let mut pos = 2;
+        while pos + 4 <= data.len() && data[pos] == 0xFF {
+            let marker = data[pos + 1];
+            if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                return Some(marker);
+            }
+            let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+            pos += 2 + length;
+        }
+        None
+    }
+
+    fn encode(progressive: bool, trellis: bool) -> Vec<u8> {
+        let img = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(32, 24, |x, y| {
+            image::Rgb([(x * 8) as u8, (y * 10) as u8, 128])
+        }));
+        let options = JpegOptions {
+            progressive,
+            trellis,
+            ..JpegOptions::default()
+        };
+        convert_to_jpeg(&img, 80, &options, None).unwrap()
+    }
+
+    #[test]
+    fn progressive_option_selects_frame_type() {
+        for trellis in [true, false] {
+            assert_eq!(frame_marker(&encode(true, trellis)), Some(0xC2));
+            assert_eq!(frame_marker(&encode(false, trellis)), Some(0xC0));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_subsampling() {
+        let img = image::DynamicImage::new_rgb8(1, 1);
+        let options = JpegOptions {
+            chroma_subsampling: "411".to_string(),
+            ..JpegOptions::default()
+        };
+        assert!(convert_to_jpeg(&img, 80, &options, None).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use img_parts::jpeg::{markers, Jpeg};
+use img_parts::Bytes;
+use jpegxl_rs::encode::{EncoderFrame, EncoderSpeed, JxlEncoder, Metadata};
+
+use crate::models::JxlOptions;
+
+use super::depth::is_high_depth;
+
+// Convert to JPEG XL, lossy (quality 0-100) or lossless, with optional EXIF box
+// "jpeg" mode falls back to lossless here (used for sources that are not JPEG)
+pub fn convert_to_jxl(
+    img: &image::DynamicImage,
+    quality: u8,
+    options: &JxlOptions,
+    exif_bytes: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    let lossless = match options.mode.as_str() {
+        "lossy" => false,
+        "lossless" | "jpeg" => true,
+        mode => return Err(format!("Unsupported JPEG XL mode: {}", mode)),
+    };
+    let has_alpha = img.has_alpha();
+    let (width, height) = (img.width(), img.height());
+
+    let mut encoder = jpegxl_rs::encoder_builder()
+        .has_alpha(has_alpha)
+        .lossless(lossless)
+        // Lossless requires keeping the original color profile
+        .uses_original_profile(lossless)
+        .quality(if lossless {
+            0.0
+        } else {
+            distance_from_quality(quality)
+        })
+        .speed(speed(options.effort))
+        // Metadata boxes need the ISOBMFF container, bare codestream otherwise
+        .use_container(exif_bytes.is_some())
+        .build()
+        .map_err(|e| format!("JPEG XL encoder setup failed: {}", e))?;
+    add_exif(&mut encoder, exif_bytes)?;
+
+    let channels = if has_alpha { 4 } else { 3 };
+    let result = if is_high_depth(img) {
+        let pixels = if has_alpha {
+            img.to_rgba16().into_raw()
+        } else {
+            img.to_rgb16().into_raw()
+        };
+        encoder.encode_frame::<u16, u16>(
+            &EncoderFrame::new(&pixels).num_channels(channels),
+            width,
+            height,
+        )
+    } else {
+        let pixels = if has_alpha {
+            img.to_rgba8().into_raw()
+        } else {
+            img.to_rgb8().into_raw()
+        };
+        encoder.encode_frame::<u8, u8>(
+            &EncoderFrame::new(&pixels).num_channels(channels),
+            width,
+            height,
+        )
+    };
+
+    result
+        .map(|encoded| encoded.data)
+        .map_err(|e| format!("JPEG XL encoding failed: {}", e))
+}
+
+// Lossless JPEG recompression: DCT coefficients are kept, and the original file
+// (including EXIF and other APP segments) can be reconstructed bit-exact
+// Without keep_metadata the APP1 segments (EXIF, XMP) are dropped first,
+// the JPEG reconstructed from the result is the original without them
+pub fn transcode_jpeg_to_jxl(
+    jpeg_data: &[u8],
+    options: &JxlOptions,
+    keep_metadata: bool,
+) -> Result<Vec<u8>, String> {
+    let stripped;
+    let jpeg_data = if keep_metadata {
+        jpeg_data
+    } else {
+        let mut jpeg = Jpeg::from_bytes(Bytes::copy_from_slice(jpeg_data))
+            .map_err(|e| format!("Failed to parse JPEG: {}", e))?;
+        jpeg.remove_segments_by_marker(markers::APP1);
+        stripped = jpeg.encoder().bytes();
+        &stripped[..]
+    };
+
+    let mut encoder = jpegxl_rs::encoder_builder()
+        .speed(speed(options.effort))
+        .use_container(true)
+        .build()
+        .map_err(|e| format!("JPEG XL encoder setup failed: {}", e))?;
+
+    encoder
+        .encode_jpeg(jpeg_data)
+        .map(|encoded| encoded.data)
+        .map_err(|e| format!("JPEG recompression failed: {}", e))
+}
+
+// Store raw EXIF (TIFF data) in an Exif box
+fn add_exif(encoder: &mut JxlEncoder, exif_bytes: Option<&[u8]>) -> Result<(), String> {
+    let Some(exif) = exif_bytes else {
+        return Ok(());
+    };
+
+    // Exif box payload starts with the offset to the TIFF header (0 = immediately after)
+    let mut payload = Vec::with_capacity(exif.len() + 4);
+    payload.extend_from_slice(&[0, 0, 0, 0]);
+    payload.extend_from_slice(exif);
+
+    encoder
+        .add_metadata(&Metadata::Exif(&payload), true)
+        .map_err(|e| format!("Failed to add EXIF to JPEG XL: {}", e))
+}
+
+// libjxl's quality (0-100) to Butteraugli distance mapping (JxlEncoderDistanceFromQuality)
+fn distance_from_quality(quality: u8) -> f32 {
+    let q = quality.min(100) as f32;
+    if q >= 30.0 {
+        0.1 + (100.0 - q) * 0.09
+    } else {
+        53.0 / 3000.0 * q * q - 23.0 / 20.0 * q + 25.0
+    }
+}
+
+// Map effort (1-10, higher = slower and smaller) to libjxl speed tier
+fn speed(effort: u8) -> EncoderSpeed {
+    match effort {
+        0 | 1 => EncoderSpeed::Lightning,
+        2 => EncoderSpeed::Thunder,
+        3 => EncoderSpeed::Falcon,
+        4 => EncoderSpeed::Cheetah,
+        5 => EncoderSpeed::Hare,
+        6 => EncoderSpeed::Wombat,
+        7 => EncoderSpeed::Squirrel,
+        8 => EncoderSpeed::Kitten,
+        9 => EncoderSpeed::Tortoise,
+        _ => EncoderSpeed::Glacier,
+    }
+}
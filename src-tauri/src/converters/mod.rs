@@ -1,9 +1,34 @@
+mod alpha;
 mod avif;
+mod avif_subsampled;
+mod basic;
+mod depth;
+mod document;
+mod encode;
+mod favicon;
+mod gif;
+mod hdr;
+mod icon;
 mod jpeg;
+mod jxl;
+mod perceptual;
 mod png;
+mod quantize;
+mod resize;
+mod target_size;
+#[cfg(test)]
+pub(crate) mod test_util;
+mod tiff;
 mod webp;
 
+pub use alpha::flatten_for_target;
 pub use avif::convert_to_avif;
+pub(crate) use depth::linear_to_srgb;
+pub use depth::{tone_map, tone_map_for_target, Gray16Image, Rgb16Image, Rgba16Image};
+pub use document::{combine_to_pdf, combine_to_tiff};
+pub use encode::{encode_animation, encode_image, encode_source, supports_animation, EncodedImage};
+pub use favicon::favicon_set;
 pub use jpeg::convert_to_jpeg;
-pub use png::convert_to_png;
+pub use jxl::convert_to_jxl;
+pub use png::{convert_to_png, BELOW_MIN_QUALITY};
 pub use webp::convert_to_webp;
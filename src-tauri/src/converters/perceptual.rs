@@ -0,0 +1,122 @@
+use crate::decode::decode_image;
+use crate::metrics::dssim;
+use crate::models::ConversionOptions;
+
+use super::encode::{encode_with_quality, supports_quality_search, EncodedImage};
+
+// Lowest quality tried when searching for the perceptual target
+const MIN_QUALITY: u8 = 10;
+
+// Encode at the lowest quality whose DSSIM against the source stays within max_dssim
+// Falls back to options.quality (upper bound) when the threshold cannot be met
+pub fn encode_to_target_dssim(
+    img: &image::DynamicImage,
+    options: &ConversionOptions,
+    max_dssim: f64,
+    exif_bytes: Option<&[u8]>,
+) -> Result<EncodedImage, String> {
+    if !supports_quality_search(options) {
+        return Err(format!(
+            "Perceptual quality target is not supported for {} (use jpeg, lossy webp, avif or lossy jxl)",
+            options.target_format
+        ));
+    }
+
+    let max_quality = options.quality.clamp(MIN_QUALITY, 100);
+
+    // Upper bound is the fallback result, encoded first
+    let (data, score) = encode_and_score(img, options, max_quality, exif_bytes)?;
+    let mut best = (max_quality, data, score);
+    if score > max_dssim {
+        return Ok(EncodedImage {
+            data: best.1,
+            quality: Some(best.0),
+            resized_to: None,
+            dssim: Some(best.2),
+        });
+    }
+
+    // Binary search for the lowest quality still meeting the threshold
+    let mut low = MIN_QUALITY;
+    let mut high = max_quality - 1;
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        let (data, score) = encode_and_score(img, options, mid, exif_bytes)?;
+
+        if score <= max_dssim {
+            best = (mid, data, score);
+            if mid == MIN_QUALITY {
+                break;
+            }
+            high = mid - 1;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    Ok(EncodedImage {
+        data: best.1,
+        quality: Some(best.0),
+        resized_to: None,
+        dssim: Some(best.2),
+    })
+}
+
+// Encode at quality, decode the result and measure DSSIM against the source
+fn encode_and_score(
+    img: &image::DynamicImage,
+    options: &ConversionOptions,
+    quality: u8,
+    exif_bytes: Option<&[u8]>,
+) -> Result<(Vec<u8>, f64), String> {
+    let data = encode_with_quality(img, options, quality, exif_bytes)?;
+    let decoded = decode_image(&data)?;
+    let score = dssim(img, &decoded)?;
+
+    Ok((data, score))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::test_util::{noise, options};
+
+    #[test]
+    fn loose_threshold_reaches_minimum_quality() {
+        let encoded =
+            encode_to_target_dssim(&noise(32, 32), &options("jpeg"), f64::MAX, None).unwrap();
+        assert_eq!(encoded.quality, Some(MIN_QUALITY));
+    }
+
+    #[test]
+    fn unreachable_threshold_falls_back_to_requested_quality() {
+        let encoded = encode_to_target_dssim(&noise(32, 32), &options("jpeg"), 0.0, None).unwrap();
+        assert_eq!(encoded.quality, Some(80));
+        assert!(encoded.dssim.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn finds_quality_between_bounds() {
+        let img = noise(32, 32);
+        let options = options("jpeg");
+        let (_, low) = encode_and_score(&img, &options, MIN_QUALITY, None).unwrap();
+        let (_, high) = encode_and_score(&img, &options, 80, None).unwrap();
+        let max_dssim = (low + high) / 2.0;
+
+        let encoded = encode_to_target_dssim(&img, &options, max_dssim, None).unwrap();
+        let quality = encoded.quality.unwrap();
+        assert!(encoded.dssim.unwrap() <= max_dssim);
+        assert!(quality > MIN_QUALITY && quality < 80, "quality {}", quality);
+    }
+
+    #[test]
+    fn single_pixel_image() {
+        let encoded = encode_to_target_dssim(&noise(1, 1), &options("jpeg"), 0.01, None).unwrap();
+        assert!(encoded.dssim.is_some());
+    }
+
+    #[test]
+    fn rejects_formats_without_quality() {
+        assert!(encode_to_target_dssim(&noise(8, 8), &options("png"), 0.01, None).is_err());
+    }
+}
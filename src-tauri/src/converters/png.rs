@@ -1,25 +1,168 @@
-use image::ImageEncoder;
+use std::collections::HashMap;
+
 use img_parts::png::Png;
 use img_parts::{Bytes, ImageEXIF};
 
-// Convert to PNG with optional EXIF preservation
+use crate::animation::Animation;
+use crate::models::PngOptions;
+
+use super::depth::{is_high_depth, to_rgba8_dithered};
+use super::quantize::{Dithering, Palette, QuantizedImage, Transparency};
+
+// Error prefix when quantize mode cannot reach quality_min (file is skipped)
+pub const BELOW_MIN_QUALITY: &str = "Palette quality below minimum";
+
+// Convert to PNG with compression level (0-9), color reduction and optional EXIF preservation
 pub fn convert_to_png(
     img: &image::DynamicImage,
-    _compression: u8,
+    compression: u8,
+    options: &PngOptions,
     exif_bytes: Option<&[u8]>,
 ) -> Result<Vec<u8>, String> {
+    let (width, height) = (img.width(), img.height());
+
+    let reduced = if options.quantize {
+        // Lossy palette mode replaces lossless color reduction
+        quantize_png(img, options)?
+    } else {
+        // 16-bit / float sources are written as 16-bit PNG unless every sample fits 8 bits
+        match sixteen_bit_pixels(img, options.reduce_color) {
+            Some(deep) => deep,
+            None => {
+                let rgba_img = img.to_rgba8();
+
+                // Pick the smallest lossless color type for these pixels
+                if options.reduce_color {
+                    reduce_color(rgba_img.as_raw(), width)
+                } else {
+                    ReducedImage {
+                        color: png::ColorType::Rgba,
+                        depth: png::BitDepth::Eight,
+                        data: rgba_img.into_raw(),
+                        palette: None,
+                        trns: None,
+                    }
+                }
+            }
+        }
+    };
+
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, width, height);
+        encoder.set_color(reduced.color);
+        encoder.set_depth(reduced.depth);
+        encoder.set_deflate_compression(deflate_compression(compression));
+        encoder.set_filter(filter(&options.filter)?);
+        if let Some(palette) = reduced.palette {
+            encoder.set_palette(palette);
+        }
+        if let Some(trns) = reduced.trns {
+            encoder.set_trns(trns);
+        }
+
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| format!("PNG encoding failed: {}", e))?;
+        writer
+            .write_image_data(&reduced.data)
+            .map_err(|e| format!("PNG encoding failed: {}", e))?;
+        writer
+            .finish()
+            .map_err(|e| format!("PNG encoding failed: {}", e))?;
+    }
+
+    // Optional oxipng pass (trials filters/deflate settings, keeps pixels intact)
+    if options.optimize {
+        buffer = oxipng::optimize_from_memory(
+            &buffer,
+            &oxipng::Options::from_preset(options.optimize_level.min(6)),
+        )
+        .map_err(|e| format!("PNG optimization failed: {}", e))?;
+    }
+
+    // Insert EXIF data if provided
+    if let Some(exif) = exif_bytes {
+        let mut png = Png::from_bytes(Bytes::copy_from_slice(&buffer))
+            .map_err(|e| format!("Failed to parse PNG: {}", e))?;
+
+        png.set_exif(Some(Bytes::copy_from_slice(exif)));
+
+        return Ok(png.encoder().bytes().to_vec());
+    }
+
+    Ok(buffer)
+}
+
+// Convert every frame to an animated PNG (APNG) with compression level and filter options
+pub fn convert_animation_to_png(
+    animation: &Animation,
+    compression: u8,
+    options: &PngOptions,
+    exif_bytes: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    let (width, height) = animation.dimensions();
+
+    // Drop the alpha channel when no frame uses it
+    let opaque = options.reduce_color
+        && animation
+            .frames
+            .iter()
+            .all(|frame| frame.image.pixels().all(|px| px.0[3] == 255));
+
     let mut buffer = Vec::new();
-    let encoder = image::codecs::png::PngEncoder::new(&mut buffer);
-
-    let rgba_img = img.to_rgba8();
-    encoder
-        .write_image(
-            rgba_img.as_raw(),
-            img.width(),
-            img.height(),
-            image::ExtendedColorType::Rgba8,
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, width, height);
+        encoder.set_color(if opaque {
+            png::ColorType::Rgb
+        } else {
+            png::ColorType::Rgba
+        });
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_deflate_compression(deflate_compression(compression));
+        encoder.set_filter(filter(&options.filter)?);
+        encoder
+            .set_animated(animation.frames.len() as u32, animation.plays)
+            .map_err(|e| format!("APNG encoding failed: {}", e))?;
+
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| format!("APNG encoding failed: {}", e))?;
+
+        // Frames are full canvas, so the default dispose (none) and blend (source) apply
+        for frame in &animation.frames {
+            let (numerator, denominator) = frame_delay(frame.delay_ms);
+            writer
+                .set_frame_delay(numerator, denominator)
+                .map_err(|e| format!("APNG encoding failed: {}", e))?;
+
+            let data = if opaque {
+                frame
+                    .image
+                    .pixels()
+                    .flat_map(|px| [px.0[0], px.0[1], px.0[2]])
+                    .collect()
+            } else {
+                frame.image.as_raw().clone()
+            };
+            writer
+                .write_image_data(&data)
+                .map_err(|e| format!("APNG encoding failed: {}", e))?;
+        }
+
+        writer
+            .finish()
+            .map_err(|e| format!("APNG encoding failed: {}", e))?;
+    }
+
+    // Optional oxipng pass (recompresses every frame, keeps animation chunks)
+    if options.optimize {
+        buffer = oxipng::optimize_from_memory(
+            &buffer,
+            &oxipng::Options::from_preset(options.optimize_level.min(6)),
         )
-        .map_err(|e| format!("PNG encoding failed: {}", e))?;
+        .map_err(|e| format!("PNG optimization failed: {}", e))?;
+    }
 
     // Insert EXIF data if provided
     if let Some(exif) = exif_bytes {
@@ -33,3 +176,382 @@ pub fn convert_to_png(
 
     Ok(buffer)
 }
+
+// APNG frame delay as a fraction of a second (u16 numerator / denominator)
+fn frame_delay(delay_ms: u32) -> (u16, u16) {
+    if delay_ms <= u16::MAX as u32 {
+        (delay_ms as u16, 1000)
+    } else {
+        ((delay_ms / 10).min(u16::MAX as u32) as u16, 100)
+    }
+}
+
+// Map 0-9 compression level to deflate settings
+fn deflate_compression(level: u8) -> png::DeflateCompression {
+    match level {
+        0 => png::DeflateCompression::NoCompression,
+        level => png::DeflateCompression::Level(level.min(9)),
+    }
+}
+
+// Map filter strategy name to png filter
+fn filter(name: &str) -> Result<png::Filter, String> {
+    match name {
+        "adaptive" => Ok(png::Filter::Adaptive),
+        "none" => Ok(png::Filter::NoFilter),
+        "sub" => Ok(png::Filter::Sub),
+        "up" => Ok(png::Filter::Up),
+        "avg" => Ok(png::Filter::Avg),
+        "paeth" => Ok(png::Filter::Paeth),
+        _ => Err(format!("Unsupported PNG filter: {}", name)),
+    }
+}
+
+// Pixel data in the reduced PNG color type
+struct ReducedImage {
+    color: png::ColorType,
+    depth: png::BitDepth,
+    data: Vec<u8>,
+    palette: Option<Vec<u8>>,
+    trns: Option<Vec<u8>>,
+}
+
+// Losslessly reduce RGBA8 pixels to grayscale, palette or RGB when possible
+fn reduce_color(rgba: &[u8], width: u32) -> ReducedImage {
+    let pixels = rgba.chunks_exact(4);
+    let opaque = pixels.clone().all(|px| px[3] == 255);
+    let grayscale = pixels.clone().all(|px| px[0] == px[1] && px[1] == px[2]);
+
+    // Opaque grayscale: 1 byte per pixel, no palette needed
+    if grayscale && opaque {
+        return ReducedImage {
+            color: png::ColorType::Grayscale,
+            depth: png::BitDepth::Eight,
+            data: pixels.map(|px| px[0]).collect(),
+            palette: None,
+            trns: None,
+        };
+    }
+
+    // 256 colors or fewer: palette with optional alpha table
+    if let Some(indexed) = build_palette(rgba, width, opaque) {
+        return indexed;
+    }
+
+    if grayscale {
+        return ReducedImage {
+            color: png::ColorType::GrayscaleAlpha,
+            depth: png::BitDepth::Eight,
+            data: pixels.flat_map(|px| [px[0], px[3]]).collect(),
+            palette: None,
+            trns: None,
+        };
+    }
+
+    if opaque {
+        return ReducedImage {
+            color: png::ColorType::Rgb,
+            depth: png::BitDepth::Eight,
+            data: pixels.flat_map(|px| [px[0], px[1], px[2]]).collect(),
+            palette: None,
+            trns: None,
+        };
+    }
+
+    ReducedImage {
+        color: png::ColorType::Rgba,
+        depth: png::BitDepth::Eight,
+        data: rgba.to_vec(),
+        palette: None,
+        trns: None,
+    }
+}
+
+// 16-bit pixel data (big-endian) for high depth sources
+// Returns None for 8-bit sources and 16-bit data that is exactly representable in 8 bits
+fn sixteen_bit_pixels(img: &image::DynamicImage, reduce: bool) -> Option<ReducedImage> {
+    if !is_high_depth(img) {
+        return None;
+    }
+
+    let rgba16 = img.to_rgba16();
+    if rgba16.as_raw().iter().all(|&v| v % 257 == 0) {
+        return None;
+    }
+
+    let pixels = rgba16.as_raw().chunks_exact(4);
+    let opaque = reduce && pixels.clone().all(|px| px[3] == u16::MAX);
+    let grayscale = reduce && pixels.clone().all(|px| px[0] == px[1] && px[1] == px[2]);
+
+    let (color, channels): (png::ColorType, &[usize]) = match (grayscale, opaque) {
+        (true, true) => (png::ColorType::Grayscale, &[0]),
+        (true, false) => (png::ColorType::GrayscaleAlpha, &[0, 3]),
+        (false, true) => (png::ColorType::Rgb, &[0, 1, 2]),
+        (false, false) => (png::ColorType::Rgba, &[0, 1, 2, 3]),
+    };
+
+    let data = pixels
+        .flat_map(|px| channels.iter().flat_map(move |&c| px[c].to_be_bytes()))
+        .collect();
+
+    Some(ReducedImage {
+        color,
+        depth: png::BitDepth::Sixteen,
+        data,
+        palette: None,
+        trns: None,
+    })
+}
+
+// Build indexed image when pixels use at most 256 distinct colors
+fn build_palette(rgba: &[u8], width: u32, opaque: bool) -> Option<ReducedImage> {
+    let mut lookup: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut colors: Vec<[u8; 4]> = Vec::new();
+    let mut indices = Vec::with_capacity(rgba.len() / 4);
+
+    for px in rgba.chunks_exact(4) {
+        let color = [px[0], px[1], px[2], px[3]];
+        let index = match lookup.get(&color) {
+            Some(&index) => index,
+            None => {
+                if colors.len() == 256 {
+                    return None;
+                }
+                let index = colors.len() as u8;
+                lookup.insert(color, index);
+                colors.push(color);
+                index
+            }
+        };
+        indices.push(index);
+    }
+
+    Some(indexed_image(&colors, &indices, width, opaque))
+}
+
+// Indexed image with the smallest bit depth and tRNS for the palette
+fn indexed_image(colors: &[[u8; 4]], indices: &[u8], width: u32, opaque: bool) -> ReducedImage {
+    // Smallest bit depth that can address every palette entry
+    let bits: u8 = match colors.len() {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    };
+    let depth = match bits {
+        1 => png::BitDepth::One,
+        2 => png::BitDepth::Two,
+        4 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+
+    let palette = colors.iter().flat_map(|c| [c[0], c[1], c[2]]).collect();
+
+    // tRNS only needs entries up to the last non-opaque color
+    let trns = if opaque {
+        None
+    } else {
+        let alphas: Vec<u8> = colors.iter().map(|c| c[3]).collect();
+        let len = alphas.iter().rposition(|&a| a != 255).map_or(0, |i| i + 1);
+        Some(alphas[..len].to_vec())
+    };
+
+    ReducedImage {
+        color: png::ColorType::Indexed,
+        depth,
+        data: pack_indices(indices, width as usize, bits),
+        palette: Some(palette),
+        trns,
+    }
+}
+
+// Lossy palette image (pngquant-style): fewest colors up to max_colors reaching quality_max
+// Fails with BELOW_MIN_QUALITY when even max_colors cannot reach quality_min
+fn quantize_png(img: &image::DynamicImage, options: &PngOptions) -> Result<ReducedImage, String> {
+    let rgba = to_rgba8_dithered(img);
+    let max_colors = options.max_colors.clamp(2, 256) as usize;
+
+    let mut palette = Palette::build(&rgba, max_colors, Transparency::Full);
+    let quality = palette.quality(&rgba);
+    if quality < options.quality_min {
+        return Err(format!(
+            "{} ({} < {})",
+            BELOW_MIN_QUALITY, quality, options.quality_min
+        ));
+    }
+
+    // Binary search the smallest palette that still reaches quality_max
+    if quality >= options.quality_max {
+        let (mut low, mut high) = (2, max_colors);
+        while low < high {
+            let middle = (low + high) / 2;
+            let candidate = Palette::build(&rgba, middle, Transparency::Full);
+            if candidate.quality(&rgba) >= options.quality_max {
+                palette = candidate;
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+    }
+
+    let dithering = if options.dither_level > 0.0 {
+        Dithering::FloydSteinberg
+    } else {
+        Dithering::None
+    };
+    let QuantizedImage {
+        palette, indices, ..
+    } = sort_by_alpha(palette.remap(&rgba, dithering, options.dither_level));
+    let opaque = palette.iter().all(|color| color[3] == 255);
+    Ok(indexed_image(&palette, &indices, rgba.width(), opaque))
+}
+
+// Move translucent entries to the front so tRNS stays short
+fn sort_by_alpha(quantized: QuantizedImage) -> QuantizedImage {
+    let mut order: Vec<usize> = (0..quantized.palette.len()).collect();
+    order.sort_by_key(|&i| quantized.palette[i][3] == 255);
+
+    let mut remap = vec![0u8; order.len()];
+    for (new, &old) in order.iter().enumerate() {
+        remap[old] = new as u8;
+    }
+
+    QuantizedImage {
+        palette: order.iter().map(|&i| quantized.palette[i]).collect(),
+        indices: quantized
+            .indices
+            .iter()
+            .map(|&i| remap[i as usize])
+            .collect(),
+        transparent: quantized.transparent.map(|i| remap[i as usize]),
+    }
+}
+
+// Pack palette indices into rows of 1/2/4/8 bits per pixel (rows padded to whole bytes)
+fn pack_indices(indices: &[u8], width: usize, bits: u8) -> Vec<u8> {
+    if bits == 8 {
+        return indices.to_vec();
+    }
+
+    let per_byte = (8 / bits) as usize;
+    let row_bytes = width.div_ceil(per_byte);
+    let mut packed = Vec::with_capacity(row_bytes * indices.len() / width.max(1));
+
+    for row in indices.chunks(width) {
+        for group in row.chunks(per_byte) {
+            let mut byte = 0u8;
+            for (i, &index) in group.iter().enumerate() {
+                // Leftmost pixel goes into the most significant bits
+                byte |= index << (8 - bits as usize * (i + 1));
+            }
+            packed.push(byte);
+        }
+    }
+
+    packed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(pixels: &[[u8; 4]]) -> Vec<u8> {
+        pixels.concat()
+    }
+
+    // Decode the encoded PNG back to RGBA8
+    fn round_trip(img: &image::DynamicImage, options: &PngOptions) -> image::RgbaImage {
+        let data = convert_to_png(img, 6, options, None).unwrap();
+        image::load_from_memory(&data).unwrap().to_rgba8()
+    }
+
+    #[test]
+    fn opaque_gray_becomes_grayscale() {
+        let reduced = reduce_color(&rgba(&[[10, 10, 10, 255], [200, 200, 200, 255]]), 2);
+        assert_eq!(reduced.color, png::ColorType::Grayscale);
+        assert_eq!(reduced.data, [10, 200]);
+    }
+
+    #[test]
+    fn few_colors_become_packed_palette() {
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        // 3 pixels per row, 1 bit each: one byte per row
+        let reduced = reduce_color(&rgba(&[red, blue, red, blue, blue, red]), 3);
+        assert_eq!(reduced.color, png::ColorType::Indexed);
+        assert_eq!(reduced.depth, png::BitDepth::One);
+        assert_eq!(reduced.data, [0b0100_0000, 0b1100_0000]);
+        assert_eq!(reduced.palette.unwrap(), [255, 0, 0, 0, 0, 255]);
+        assert_eq!(reduced.trns, None);
+    }
+
+    #[test]
+    fn fully_transparent_image_keeps_alpha() {
+        let reduced = reduce_color(&rgba(&[[0, 0, 0, 0]; 4]), 2);
+        assert_eq!(reduced.color, png::ColorType::Indexed);
+        assert_eq!(reduced.trns.unwrap(), [0]);
+
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::new(3, 2));
+        assert!(round_trip(&img, &PngOptions::default())
+            .pixels()
+            .all(|px| px.0[3] == 0));
+    }
+
+    #[test]
+    fn many_colors_keep_full_color_type() {
+        let colors: Vec<[u8; 4]> = (0..300u32)
+            .map(|i| [i as u8, (i >> 8) as u8, 7, 255])
+            .collect();
+        assert_eq!(reduce_color(&rgba(&colors), 30).color, png::ColorType::Rgb);
+
+        let translucent: Vec<[u8; 4]> = colors.iter().map(|c| [c[0], c[1], c[2], 128]).collect();
+        assert_eq!(
+            reduce_color(&rgba(&translucent), 30).color,
+            png::ColorType::Rgba
+        );
+
+        let gray: Vec<[u8; 4]> = (0..300u32)
+            .map(|i| [i as u8, i as u8, i as u8, (i / 2) as u8])
+            .collect();
+        assert_eq!(
+            reduce_color(&rgba(&gray), 30).color,
+            png::ColorType::GrayscaleAlpha
+        );
+    }
+
+    #[test]
+    fn reduced_pixels_round_trip() {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(5, 3, |x, y| {
+            image::Rgba([
+                (x * 60) as u8,
+                (y * 100) as u8,
+                0,
+                if x == 0 { 0 } else { 255 },
+            ])
+        }));
+        assert_eq!(round_trip(&img, &PngOptions::default()), img.to_rgba8());
+
+        let pixel = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([1, 2, 3, 4]),
+        ));
+        assert_eq!(round_trip(&pixel, &PngOptions::default()), pixel.to_rgba8());
+    }
+
+    #[test]
+    fn quantize_mode_fails_below_minimum_quality() {
+        let img = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, ((x + y) * 2) as u8])
+        }));
+        let options = PngOptions {
+            quantize: true,
+            max_colors: 2,
+            quality_min: 100,
+            ..PngOptions::default()
+        };
+        let err = convert_to_png(&img, 6, &options, None).unwrap_err();
+        assert!(err.starts_with(BELOW_MIN_QUALITY), "{}", err);
+    }
+}
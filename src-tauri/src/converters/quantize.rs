@@ -0,0 +1,450 @@
+use std::collections::{HashMap, HashSet};
+
+use color_quant::NeuQuant;
+use image::RgbaImage;
+
+use super::depth::BAYER_4X4;
+
+// NeuQuant trains on every n-th pixel, n chosen to sample about this many
+const TRAINING_PIXELS: usize = 1_000_000;
+
+#[derive(Clone, Copy)]
+pub enum Dithering {
+    None,
+    FloydSteinberg,
+    Ordered,
+}
+
+impl Dithering {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(Dithering::None),
+            "floyd-steinberg" => Ok(Dithering::FloydSteinberg),
+            "ordered" => Ok(Dithering::Ordered),
+            other => Err(format!("Unknown dithering: {}", other)),
+        }
+    }
+}
+
+// How alpha is represented in the palette
+#[derive(Clone, Copy)]
+pub enum Transparency {
+    Binary(u8), // Alpha below the threshold is transparent, everything else opaque (GIF)
+    Full,       // Partial alpha kept per entry, alpha 0 pixels share one entry (PNG)
+}
+
+// Palette image: one index per pixel, row-major
+pub struct QuantizedImage {
+    pub palette: Vec<[u8; 4]>,   // RGBA entries
+    pub indices: Vec<u8>,        // Index into palette per pixel
+    pub transparent: Option<u8>, // Entry shared by all transparent pixels
+}
+
+// Palette built for an image, maps colors to their nearest entry
+pub struct Palette {
+    colors: Vec<[u8; 4]>,
+    transparent: Option<u8>,
+    transparency: Transparency,
+    mapper: Mapper,
+}
+
+impl Palette {
+    // At most max_colors (2-256) entries
+    // Transparent pixels get one reserved entry so they never pick up visible colors
+    pub fn build(img: &RgbaImage, max_colors: usize, transparency: Transparency) -> Palette {
+        let visible: Vec<[u8; 4]> = img
+            .pixels()
+            .filter_map(|pixel| visible_color(pixel, transparency))
+            .collect();
+        let has_transparent = visible.len() < (img.width() * img.height()) as usize;
+        let max_colors = max_colors.clamp(2, 256) - usize::from(has_transparent);
+
+        let (mut colors, mapper) = match exact_palette(&visible, max_colors) {
+            Some(colors) => {
+                let lookup = colors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &color)| (color, i as u8))
+                    .collect();
+                (colors, Mapper::Exact(lookup))
+            }
+            None => {
+                let pixels: Vec<u8> = visible.iter().flatten().copied().collect();
+                // Sampling factor 1 (every pixel) to 30 (fastest)
+                let sample_factor = (visible.len() / TRAINING_PIXELS + 1).min(30) as i32;
+                let network = NeuQuant::new(sample_factor, max_colors, &pixels);
+                let colors = network
+                    .color_map_rgba()
+                    .chunks_exact(4)
+                    .map(|c| [c[0], c[1], c[2], c[3]])
+                    .collect();
+                (colors, Mapper::Network(network))
+            }
+        };
+
+        let transparent = if has_transparent {
+            colors.push([0, 0, 0, 0]);
+            Some((colors.len() - 1) as u8)
+        } else {
+            None
+        };
+
+        Palette {
+            colors,
+            transparent,
+            transparency,
+            mapper,
+        }
+    }
+
+    // pngquant-style quality (0-100) of the undithered mapping
+    // Based on the mean squared error of alpha-premultiplied RGBA, like libimagequant
+    pub fn quality(&self, img: &RgbaImage) -> u8 {
+        let premultiplied = |[r, g, b, a]: [u8; 4]| {
+            let alpha = a as f64 / 255.0;
+            [r, g, b]
+                .map(|v| v as f64 / 255.0 * alpha)
+                .into_iter()
+                .chain([alpha])
+        };
+
+        let total: f64 = img
+            .pixels()
+            .map(|pixel| {
+                let entry = match visible_color(pixel, self.transparency) {
+                    Some(color) => self.colors[self.mapper.index_of(color) as usize],
+                    None => [0, 0, 0, 0],
+                };
+                premultiplied(pixel.0)
+                    .zip(premultiplied(entry))
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum::<f64>()
+            })
+            .sum();
+        let mse = total / (img.width() as f64 * img.height() as f64).max(1.0);
+
+        (0..=100)
+            .rev()
+            .find(|&quality| mse <= quality_to_mse(quality))
+            .unwrap_or(0)
+    }
+
+    // Map every pixel to an entry, level (0.0 - 1.0) scales the dithering strength
+    pub fn remap(self, img: &RgbaImage, dithering: Dithering, level: f32) -> QuantizedImage {
+        // Exact palettes reproduce every color, nothing to dither
+        let dithering = match self.mapper {
+            Mapper::Exact(_) => Dithering::None,
+            Mapper::Network(_) => dithering,
+        };
+
+        let indices = remap(
+            img,
+            &self.colors,
+            &self.mapper,
+            dithering,
+            level.clamp(0.0, 1.0),
+            |pixel| visible_color(pixel, self.transparency),
+        )
+        .into_iter()
+        .map(|index| index.or(self.transparent).unwrap_or(0))
+        .collect();
+
+        QuantizedImage {
+            palette: self.colors,
+            indices,
+            transparent: self.transparent,
+        }
+    }
+}
+
+// Build a palette and map the image in one step
+pub fn quantize(
+    img: &RgbaImage,
+    max_colors: usize,
+    dithering: Dithering,
+    dither_level: f32,
+    transparency: Transparency,
+) -> QuantizedImage {
+    Palette::build(img, max_colors, transparency).remap(img, dithering, dither_level)
+}
+
+// Color used for palette matching, None for pixels mapped to the transparent entry
+fn visible_color(pixel: &image::Rgba<u8>, transparency: Transparency) -> Option<[u8; 4]> {
+    let [r, g, b, a] = pixel.0;
+    match transparency {
+        Transparency::Binary(threshold) if a < threshold => None,
+        Transparency::Binary(_) => Some([r, g, b, 255]),
+        Transparency::Full if a == 0 => None,
+        Transparency::Full => Some([r, g, b, a]),
+    }
+}
+
+// Largest mean squared error accepted for a quality (libimagequant's scale)
+fn quality_to_mse(quality: u8) -> f64 {
+    match quality {
+        0 => f64::MAX,
+        100.. => 0.0,
+        quality => {
+            let q = quality as f64;
+            let low_quality_fudge = (0.016 / (0.001 + q) - 0.001).max(0.0);
+            0.45 * (low_quality_fudge + 2.5 / (210.0 + q).powf(1.2) * (100.1 - q) / 100.0)
+        }
+    }
+}
+
+// Nearest palette entry lookup
+enum Mapper {
+    Exact(HashMap<[u8; 4], u8>),
+    Network(NeuQuant),
+}
+
+impl Mapper {
+    fn index_of(&self, color: [u8; 4]) -> u8 {
+        match self {
+            Mapper::Exact(lookup) => lookup.get(&color).copied().unwrap_or(0),
+            Mapper::Network(network) => network.index_of(&color) as u8,
+        }
+    }
+}
+
+// Palette of the distinct colors, None when there are more than max_colors
+fn exact_palette(pixels: &[[u8; 4]], max_colors: usize) -> Option<Vec<[u8; 4]>> {
+    let mut seen = HashSet::new();
+    let mut palette = Vec::new();
+    for &color in pixels {
+        if seen.insert(color) {
+            if palette.len() == max_colors {
+                return None;
+            }
+            palette.push(color);
+        }
+    }
+
+    Some(palette)
+}
+
+// Map every pixel to a palette index, None for pixels source() skips (transparent)
+fn remap(
+    img: &RgbaImage,
+    palette: &[[u8; 4]],
+    mapper: &Mapper,
+    dithering: Dithering,
+    level: f32,
+    source: impl Fn(&image::Rgba<u8>) -> Option<[u8; 4]>,
+) -> Vec<Option<u8>> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let mut indices = vec![None; width * height];
+
+    match dithering {
+        Dithering::None => {
+            for (index, pixel) in indices.iter_mut().zip(img.pixels()) {
+                *index = source(pixel).map(|color| mapper.index_of(color));
+            }
+        }
+        Dithering::Ordered => {
+            // Threshold spread of roughly one palette step per channel
+            let spread = 255.0 / (palette.len() as f32).cbrt() * level;
+            for (i, (index, pixel)) in indices.iter_mut().zip(img.pixels()).enumerate() {
+                let (x, y) = (i % width, i / width);
+                let offset = ((BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5) * spread;
+                *index = source(pixel).map(|mut color| {
+                    for value in &mut color[..3] {
+                        *value = (*value as f32 + offset).round().clamp(0.0, 255.0) as u8;
+                    }
+                    mapper.index_of(color)
+                });
+            }
+        }
+        Dithering::FloydSteinberg => {
+            // Error rows padded by one pixel on each side, serpentine scan
+            let mut current = vec![[0f32; 4]; width + 2];
+            let mut next = vec![[0f32; 4]; width + 2];
+            for y in 0..height {
+                let reverse = y % 2 == 1;
+                for step in 0..width {
+                    let x = if reverse { width - 1 - step } else { step };
+                    let Some(color) = source(img.get_pixel(x as u32, y as u32)) else {
+                        continue;
+                    };
+
+                    let target: [u8; 4] = std::array::from_fn(|c| {
+                        (color[c] as f32 + current[x + 1][c])
+                            .round()
+                            .clamp(0.0, 255.0) as u8
+                    });
+                    let index = mapper.index_of(target);
+                    indices[y * width + x] = Some(index);
+
+                    let chosen = palette[index as usize];
+                    let (ahead, behind) = if reverse { (x, x + 2) } else { (x + 2, x) };
+                    let errors: [f32; 4] =
+                        std::array::from_fn(|c| (target[c] as f32 - chosen[c] as f32) * level);
+                    for (c, error) in errors.into_iter().enumerate() {
+                        current[ahead][c] += error * 7.0 / 16.0;
+                        next[behind][c] += error * 3.0 / 16.0;
+                        next[x + 1][c] += error * 5.0 / 16.0;
+                        next[ahead][c] += error / 16.0;
+                    }
+                }
+                std::mem::swap(&mut current, &mut next);
+                next.fill([0.0; 4]);
+            }
+        }
+    }
+
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Smooth gradient with far more colors than any palette
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([
+                (x * 255 / width.max(2).saturating_sub(1)) as u8,
+                (y * 255 / height.max(2).saturating_sub(1)) as u8,
+                96,
+                255,
+            ])
+        })
+    }
+
+    fn colors(quantized: &QuantizedImage) -> Vec<[u8; 4]> {
+        quantized
+            .indices
+            .iter()
+            .map(|&i| quantized.palette[i as usize])
+            .collect()
+    }
+
+    // Mean difference of 4x4 block averages, what the eye sees from a distance
+    fn block_error(img: &RgbaImage, quantized: &QuantizedImage) -> f64 {
+        let mapped = colors(quantized);
+        let width = img.width() as usize;
+        let mut total = 0.0;
+        let mut blocks = 0;
+        for by in (0..img.height() as usize).step_by(4) {
+            for bx in (0..width).step_by(4) {
+                let block = || (by..by + 4).flat_map(|y| (bx..bx + 4).map(move |x| (x, y)));
+                total += (0..3)
+                    .map(|c| {
+                        let (source, result) = block().fold((0.0, 0.0), |(s, r), (x, y)| {
+                            (
+                                s + img.get_pixel(x as u32, y as u32).0[c] as f64,
+                                r + mapped[y * width + x][c] as f64,
+                            )
+                        });
+                        (source - result).abs() / 16.0
+                    })
+                    .sum::<f64>();
+                blocks += 3;
+            }
+        }
+        total / blocks as f64
+    }
+
+    #[test]
+    fn few_colors_are_kept_exactly() {
+        let img = RgbaImage::from_fn(4, 4, |x, y| {
+            image::Rgba([(x * 80) as u8, if y < 2 { 0 } else { 255 }, 0, 255])
+        });
+        let quantized = quantize(&img, 16, Dithering::FloydSteinberg, 1.0, Transparency::Full);
+        assert_eq!(quantized.palette.len(), 8);
+        assert_eq!(quantized.transparent, None);
+        assert_eq!(
+            colors(&quantized),
+            img.pixels().map(|px| px.0).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn single_pixel_image() {
+        let img = RgbaImage::from_pixel(1, 1, image::Rgba([12, 34, 56, 255]));
+        let quantized = quantize(&img, 256, Dithering::None, 1.0, Transparency::Full);
+        assert_eq!(quantized.palette, [[12, 34, 56, 255]]);
+        assert_eq!(quantized.indices, [0]);
+    }
+
+    #[test]
+    fn fully_transparent_image_uses_one_entry() {
+        let img = RgbaImage::new(5, 3);
+        let palette = Palette::build(&img, 256, Transparency::Full);
+        assert_eq!(palette.quality(&img), 100);
+
+        let quantized = palette.remap(&img, Dithering::FloydSteinberg, 1.0);
+        assert_eq!(quantized.palette, [[0, 0, 0, 0]]);
+        assert_eq!(quantized.transparent, Some(0));
+        assert!(quantized.indices.iter().all(|&i| i == 0));
+    }
+
+    #[test]
+    fn binary_transparency_uses_threshold() {
+        let img = RgbaImage::from_fn(2, 1, |x, _| {
+            image::Rgba([200, 10, 10, [100, 200][x as usize]])
+        });
+        let quantized = quantize(&img, 256, Dithering::None, 1.0, Transparency::Binary(128));
+        let transparent = quantized.transparent.unwrap();
+        assert_eq!(quantized.indices[0], transparent);
+        assert_eq!(
+            quantized.palette[quantized.indices[1] as usize],
+            [200, 10, 10, 255]
+        );
+    }
+
+    #[test]
+    fn network_palette_respects_max_colors() {
+        let img = gradient(64, 64);
+        // More entries can only get closer to a gradient
+        let large = Palette::build(&img, 128, Transparency::Full).quality(&img);
+        let palette = Palette::build(&img, 16, Transparency::Full);
+        let quality = palette.quality(&img);
+        assert!(quality < large && large < 100, "{} / {}", quality, large);
+
+        let quantized = palette.remap(&img, Dithering::None, 1.0);
+        assert!(quantized.palette.len() <= 16);
+        assert!(quantized
+            .indices
+            .iter()
+            .all(|&i| (i as usize) < quantized.palette.len()));
+    }
+
+    #[test]
+    fn tiny_image_with_more_colors_than_entries() {
+        let img = RgbaImage::from_fn(3, 1, |x, _| image::Rgba([(x * 120) as u8, 0, 0, 255]));
+        let quantized = quantize(&img, 2, Dithering::FloydSteinberg, 1.0, Transparency::Full);
+        assert!(quantized.palette.len() <= 2);
+        assert_eq!(quantized.indices.len(), 3);
+    }
+
+    #[test]
+    fn dithering_keeps_local_average_closer() {
+        let img = gradient(64, 32);
+        let plain = quantize(&img, 4, Dithering::None, 1.0, Transparency::Full);
+        let plain_error = block_error(&img, &plain);
+        for dithering in [Dithering::FloydSteinberg, Dithering::Ordered] {
+            let dithered = quantize(&img, 4, dithering, 1.0, Transparency::Full);
+            assert!(block_error(&img, &dithered) < plain_error);
+        }
+    }
+
+    #[test]
+    fn zero_level_disables_dithering() {
+        let img = gradient(32, 32);
+        let plain = quantize(&img, 8, Dithering::None, 1.0, Transparency::Full);
+        for dithering in [Dithering::FloydSteinberg, Dithering::Ordered] {
+            let quantized = quantize(&img, 8, dithering, 0.0, Transparency::Full);
+            assert_eq!(quantized.indices, plain.indices);
+        }
+    }
+
+    #[test]
+    fn parses_dithering_names() {
+        assert!(matches!(
+            Dithering::parse("ordered"),
+            Ok(Dithering::Ordered)
+        ));
+        assert!(Dithering::parse("random").is_err());
+    }
+}
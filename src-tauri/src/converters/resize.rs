@@ -0,0 +1,43 @@
+use image::imageops::FilterType;
+
+use super::depth::to_rgba8_dithered;
+
+// Downscale image by a factor (0.0 - 1.0), keeping at least 1px per side
+pub fn downscale(img: &image::DynamicImage, scale: f32) -> image::DynamicImage {
+    let width = ((img.width() as f32 * scale).round() as u32).max(1);
+    let height = ((img.height() as f32 * scale).round() as u32).max(1);
+
+    img.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+// Resample to fit inside a size x size square, leaving padding (fraction of size) on
+// each side, centered on a background color (transparent when None)
+pub fn fit_square(
+    img: &image::DynamicImage,
+    size: u32,
+    padding: f32,
+    background: Option<[u8; 3]>,
+) -> image::RgbaImage {
+    let inner = ((size as f32 * (1.0 - 2.0 * padding.clamp(0.0, 0.45))).round() as u32).max(1);
+    let resized = to_rgba8_dithered(&img.resize(inner, inner, FilterType::Lanczos3));
+    if background.is_none() && resized.dimensions() == (size, size) {
+        return resized;
+    }
+
+    let fill = match background {
+        Some([r, g, b]) => image::Rgba([r, g, b, 255]),
+        None => image::Rgba([0, 0, 0, 0]),
+    };
+    let mut canvas = image::RgbaImage::from_pixel(size, size, fill);
+    let x = (size - resized.width()) as i64 / 2;
+    let y = (size - resized.height()) as i64 / 2;
+    // Blend over an opaque background, copy as-is onto a transparent one
+    if background.is_some() {
+        image::imageops::overlay(&mut canvas, &resized, x, y);
+        // Blending can round alpha to 254
+        canvas.pixels_mut().for_each(|pixel| pixel[3] = 255);
+    } else {
+        image::imageops::replace(&mut canvas, &resized, x, y);
+    }
+    canvas
+}
@@ -0,0 +1,187 @@
+use crate::models::ConversionOptions;
+
+use super::encode::{encode_with_quality, supports_quality_search, EncodedImage};
+use super::resize::downscale;
+
+// Lowest quality tried before falling back to downscaling
+const MIN_QUALITY: u8 = 10;
+
+// Scale factor applied per downscaling round
+const DOWNSCALE_STEP: f32 = 0.85;
+
+// Smallest side length allowed when downscaling
+const MIN_DIMENSION: u32 = 16;
+
+// Encode with the highest quality whose output fits in max_size bytes
+// Binary-searches quality (MIN_QUALITY..=options.quality), then downscales as a last resort
+pub fn encode_to_target_size(
+    img: &image::DynamicImage,
+    options: &ConversionOptions,
+    max_size: u64,
+    exif_bytes: Option<&[u8]>,
+) -> Result<EncodedImage, String> {
+    if !supports_quality_search(options) {
+        return Err(format!(
+            "Target file size is not supported for {} (use jpeg, lossy webp, avif or lossy jxl)",
+            options.target_format
+        ));
+    }
+
+    // Requested quality acts as the upper bound of the search
+    let max_quality = options.quality.clamp(MIN_QUALITY, 100);
+
+    let mut scale = 1.0_f32;
+    let mut scaled: Option<image::DynamicImage> = None;
+
+    loop {
+        let current = scaled.as_ref().unwrap_or(img);
+
+        if let Some((quality, data)) =
+            search_quality(current, options, max_quality, max_size, exif_bytes)?
+        {
+            return Ok(EncodedImage {
+                data,
+                quality: Some(quality),
+                resized_to: scaled.as_ref().map(|s| (s.width(), s.height())),
+                dssim: None,
+            });
+        }
+
+        // Even MIN_QUALITY is too large: downscale if allowed
+        if !options.allow_downscale {
+            return Err(format!(
+                "Cannot reach target size of {} bytes (even at quality {})",
+                max_size, MIN_QUALITY
+            ));
+        }
+
+        scale *= DOWNSCALE_STEP;
+        let next = downscale(img, scale);
+        if next.width() < MIN_DIMENSION || next.height() < MIN_DIMENSION {
+            return Err(format!(
+                "Cannot reach target size of {} bytes (even after downscaling)",
+                max_size
+            ));
+        }
+        scaled = Some(next);
+    }
+}
+
+// Find the highest quality in MIN_QUALITY..=max_quality that fits, or None if nothing fits
+fn search_quality(
+    img: &image::DynamicImage,
+    options: &ConversionOptions,
+    max_quality: u8,
+    max_size: u64,
+    exif_bytes: Option<&[u8]>,
+) -> Result<Option<(u8, Vec<u8>)>, String> {
+    // Fast path: requested quality already fits
+    let data = encode_with_quality(img, options, max_quality, exif_bytes)?;
+    if data.len() as u64 <= max_size {
+        return Ok(Some((max_quality, data)));
+    }
+
+    // Lower bound must fit, otherwise searching is pointless
+    let data = encode_with_quality(img, options, MIN_QUALITY, exif_bytes)?;
+    if data.len() as u64 > max_size {
+        return Ok(None);
+    }
+    let mut best = (MIN_QUALITY, data);
+
+    // Binary search between known fitting (best) and known too large (max_quality)
+    let mut low = MIN_QUALITY + 1;
+    let mut high = max_quality - 1;
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        let data = encode_with_quality(img, options, mid, exif_bytes)?;
+
+        if data.len() as u64 <= max_size {
+            best = (mid, data);
+            low = mid + 1;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    Ok(Some(best))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::test_util::{self, noise};
+
+    fn options(target_format: &str, allow_downscale: bool) -> ConversionOptions {
+        ConversionOptions {
+            allow_downscale,
+            ..test_util::options(target_format)
+        }
+    }
+
+    fn size_at(img: &image::DynamicImage, options: &ConversionOptions, quality: u8) -> u64 {
+        encode_with_quality(img, options, quality, None)
+            .unwrap()
+            .len() as u64
+    }
+
+    #[test]
+    fn keeps_requested_quality_when_it_fits() {
+        let img = noise(64, 64);
+        let encoded = encode_to_target_size(&img, &options("jpeg", false), u64::MAX, None).unwrap();
+        assert_eq!(encoded.quality, Some(80));
+        assert_eq!(encoded.resized_to, None);
+    }
+
+    #[test]
+    fn finds_highest_quality_that_fits() {
+        let img = noise(64, 64);
+        let options = options("jpeg", false);
+        let max_size = (size_at(&img, &options, MIN_QUALITY) + size_at(&img, &options, 80)) / 2;
+
+        let encoded = encode_to_target_size(&img, &options, max_size, None).unwrap();
+        let quality = encoded.quality.unwrap();
+        assert!(encoded.data.len() as u64 <= max_size);
+        assert!((MIN_QUALITY..80).contains(&quality), "quality {}", quality);
+        assert!(size_at(&img, &options, quality + 1) > max_size);
+    }
+
+    #[test]
+    fn downscales_when_minimum_quality_is_too_large() {
+        let img = noise(128, 128);
+        let options = options("jpeg", true);
+        let max_size = size_at(&img, &options, MIN_QUALITY) * 3 / 4;
+
+        let encoded = encode_to_target_size(&img, &options, max_size, None).unwrap();
+        let (width, height) = encoded.resized_to.unwrap();
+        assert!(width < 128 && height < 128);
+        assert!(encoded.data.len() as u64 <= max_size);
+    }
+
+    #[test]
+    fn unreachable_target_fails() {
+        let img = noise(64, 64);
+        let err = encode_to_target_size(&img, &options("jpeg", false), 1, None)
+            .err()
+            .expect("target is unreachable");
+        assert!(err.contains("even at quality"), "{}", err);
+        let err = encode_to_target_size(&img, &options("jpeg", true), 1, None)
+            .err()
+            .expect("target is unreachable");
+        assert!(err.contains("even after downscaling"), "{}", err);
+    }
+
+    #[test]
+    fn single_pixel_image() {
+        let img = noise(1, 1);
+        let encoded = encode_to_target_size(&img, &options("jpeg", true), u64::MAX, None).unwrap();
+        assert_eq!(encoded.quality, Some(80));
+        // Too small to downscale any further
+        assert!(encode_to_target_size(&img, &options("jpeg", true), 1, None).is_err());
+    }
+
+    #[test]
+    fn rejects_formats_without_quality() {
+        let err = encode_to_target_size(&noise(8, 8), &options("png", false), 1000, None);
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,21 @@
+// Fixtures shared by the converter tests
+use crate::models::ConversionOptions;
+
+// Hashed noise, so every quality step changes the encoded size and the decoded image
+pub fn noise(width: u32, height: u32) -> image::DynamicImage {
+    image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| {
+        let h =
+            (x.wrapping_mul(0x9E37_79B9) ^ y.wrapping_mul(0x85EB_CA6B)).wrapping_mul(0xC2B2_AE35);
+        image::Rgb([(h >> 24) as u8, (h >> 16) as u8, (h >> 8) as u8])
+    }))
+}
+
+// Required fields as the frontend sends them, everything else at its default
+pub fn options(target_format: &str) -> ConversionOptions {
+    serde_json::from_value(serde_json::json!({
+        "target_format": target_format,
+        "quality": 80,
+        "avif_speed": 10,
+    }))
+    .unwrap()
+}
@@ -0,0 +1,118 @@
+use std::io::{Cursor, Seek, Write};
+
+use image::DynamicImage;
+use tiff::encoder::colortype::{ColorType, Gray16, Gray8, RGB16, RGB8, RGBA16, RGBA8};
+use tiff::encoder::{Compression, DeflateLevel, Rational, TiffEncoder, TiffValue};
+use tiff::tags::{Predictor, ResolutionUnit};
+
+use crate::models::TiffOptions;
+
+use super::depth::{is_high_depth, reduce_to_8bit};
+
+// Single-page TIFF with the chosen compression, predictor, bit depth and resolution
+pub fn convert_to_tiff(img: &DynamicImage, options: &TiffOptions) -> Result<Vec<u8>, String> {
+    let mut buffer = Cursor::new(Vec::new());
+    let mut encoder = tiff_encoder(&mut buffer, options)?;
+    write_page(&mut encoder, img, options)?;
+    Ok(buffer.into_inner())
+}
+
+// Encoder for one or more pages (see write_page)
+pub(super) fn tiff_encoder<W: Write + Seek>(
+    writer: W,
+    options: &TiffOptions,
+) -> Result<TiffEncoder<W>, String> {
+    let compression = match options.compression.as_str() {
+        "none" => Compression::Uncompressed,
+        "lzw" => Compression::Lzw,
+        "deflate" => Compression::Deflate(DeflateLevel::Balanced),
+        "packbits" => Compression::Packbits,
+        other => return Err(format!("Unsupported TIFF compression: {}", other)),
+    };
+    // Differencing only helps the dictionary / deflate coders
+    let predictor =
+        if options.predictor && matches!(options.compression.as_str(), "lzw" | "deflate") {
+            Predictor::Horizontal
+        } else {
+            Predictor::None
+        };
+
+    Ok(TiffEncoder::new(writer)
+        .map_err(|e| format!("TIFF encoding failed: {}", e))?
+        .with_compression(compression)
+        .with_predictor(predictor))
+}
+
+// Append the image as the next page, stored as gray, RGB or RGBA at 8 or 16 bits
+// (gray + alpha is stored as RGBA, float sources as 16-bit)
+pub(super) fn write_page<W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    img: &DynamicImage,
+    options: &TiffOptions,
+) -> Result<(), String> {
+    let sixteen_bit = match options.bit_depth {
+        0 => is_high_depth(img),
+        8 => false,
+        16 => true,
+        other => return Err(format!("TIFF bit depth must be 8 or 16 ({})", other)),
+    };
+    let (width, height) = (img.width(), img.height());
+
+    let result = if sixteen_bit {
+        match img {
+            DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) => {
+                let gray = img.to_luma16();
+                write_pixels::<_, Gray16>(encoder, width, height, gray.as_raw(), options)
+            }
+            img if img.has_alpha() => {
+                let rgba = img.to_rgba16();
+                write_pixels::<_, RGBA16>(encoder, width, height, rgba.as_raw(), options)
+            }
+            img => {
+                let rgb = img.to_rgb16();
+                write_pixels::<_, RGB16>(encoder, width, height, rgb.as_raw(), options)
+            }
+        }
+    } else {
+        // 16-bit / float sources are dithered instead of truncated
+        let reduced = reduce_to_8bit(img);
+        match reduced.as_ref().unwrap_or(img) {
+            DynamicImage::ImageLuma8(gray) => {
+                write_pixels::<_, Gray8>(encoder, width, height, gray.as_raw(), options)
+            }
+            DynamicImage::ImageRgb8(rgb) => {
+                write_pixels::<_, RGB8>(encoder, width, height, rgb.as_raw(), options)
+            }
+            img => {
+                let rgba = img.to_rgba8();
+                write_pixels::<_, RGBA8>(encoder, width, height, rgba.as_raw(), options)
+            }
+        }
+    };
+
+    result.map_err(|e| format!("TIFF encoding failed: {}", e))
+}
+
+fn write_pixels<W: Write + Seek, C: ColorType>(
+    encoder: &mut TiffEncoder<W>,
+    width: u32,
+    height: u32,
+    pixels: &[C::Inner],
+    options: &TiffOptions,
+) -> tiff::TiffResult<()>
+where
+    [C::Inner]: TiffValue,
+{
+    let mut page = encoder.new_image::<C>(width, height)?;
+    // Without a DPI the encoder writes 1/1 with no unit
+    if options.dpi > 0 {
+        page.resolution(
+            ResolutionUnit::Inch,
+            Rational {
+                n: options.dpi,
+                d: 1,
+            },
+        );
+    }
+    page.write_data(pixels)
+}
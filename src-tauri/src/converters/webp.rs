@@ -1,23 +1,172 @@
 use img_parts::webp::WebP;
 use img_parts::{Bytes, ImageEXIF};
+use libwebp_sys::{
+    WebPAnimEncoder, WebPAnimEncoderAdd, WebPAnimEncoderAssemble, WebPAnimEncoderDelete,
+    WebPAnimEncoderGetError, WebPAnimEncoderNewInternal, WebPAnimEncoderOptions,
+    WebPAnimEncoderOptionsInitInternal, WebPConfig, WebPData, WebPDataClear, WebPPicture,
+    WebPPictureFree, WebPPictureImportRGBA, WebPPreset, WEBP_MUX_ABI_VERSION,
+};
 
-// Convert to WebP using libwebp with optional EXIF preservation
+use crate::animation::Animation;
+use crate::models::WebpOptions;
+
+use super::depth::to_rgba8_dithered;
+
+// Convert to WebP using libwebp (lossy, lossless or near-lossless) with optional EXIF preservation
 pub fn convert_to_webp(
     img: &image::DynamicImage,
     quality: u8,
+    options: &WebpOptions,
     exif_bytes: Option<&[u8]>,
 ) -> Result<Vec<u8>, String> {
-    use webp::Encoder;
-
-    let rgba_img = img.to_rgba8();
+    let rgba_img = to_rgba8_dithered(img);
     let (width, height) = rgba_img.dimensions();
 
-    let encoder = Encoder::from_rgba(&rgba_img, width, height);
-    let webp_data = encoder.encode(quality as f32);
+    let config = webp_config(quality, options)?;
+
+    let encoder = webp::Encoder::from_rgba(&rgba_img, width, height);
+    let webp_data = encoder
+        .encode_advanced(&config)
+        .map_err(|e| format!("WebP encoding failed: {:?}", e))?;
+
+    insert_exif(&webp_data, exif_bytes)
+}
+
+// Convert every frame to an animated WebP (libwebp picks sub-rectangles and blending)
+pub fn convert_animation_to_webp(
+    animation: &Animation,
+    quality: u8,
+    options: &WebpOptions,
+    exif_bytes: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    let (width, height) = animation.dimensions();
+    let config = webp_config(quality, options)?;
+
+    // SAFETY: every libwebp object is initialized before use and released on all paths
+    let webp_data = unsafe {
+        let mut anim_options = std::mem::MaybeUninit::<WebPAnimEncoderOptions>::uninit();
+        if WebPAnimEncoderOptionsInitInternal(
+            anim_options.as_mut_ptr(),
+            WEBP_MUX_ABI_VERSION as i32,
+        ) == 0
+        {
+            return Err("Failed to initialize WebP animation options".to_string());
+        }
+        let mut anim_options = anim_options.assume_init();
+        anim_options.anim_params.loop_count = animation.plays as i32;
+
+        let encoder = AnimEncoder(WebPAnimEncoderNewInternal(
+            width as i32,
+            height as i32,
+            &anim_options,
+            WEBP_MUX_ABI_VERSION as i32,
+        ));
+        if encoder.0.is_null() {
+            return Err("Failed to create WebP animation encoder".to_string());
+        }
+
+        // Frames are added at their start time
+        let mut timestamp: i32 = 0;
+        for frame in &animation.frames {
+            let mut picture =
+                WebPPicture::new().map_err(|_| "Failed to initialize WebP picture".to_string())?;
+            picture.use_argb = 1;
+            picture.width = width as i32;
+            picture.height = height as i32;
+
+            let imported =
+                WebPPictureImportRGBA(&mut picture, frame.image.as_ptr(), (width * 4) as i32);
+            let added = imported != 0
+                && WebPAnimEncoderAdd(encoder.0, &mut picture, timestamp, &config) != 0;
+            WebPPictureFree(&mut picture);
+            if !added {
+                return Err(format!(
+                    "WebP animation encoding failed: {}",
+                    encoder.error()
+                ));
+            }
 
-    // Insert EXIF data if provided
+            timestamp = timestamp.saturating_add(frame.delay_ms as i32);
+        }
+
+        // Final call with the end time sets the duration of the last frame
+        if WebPAnimEncoderAdd(encoder.0, std::ptr::null_mut(), timestamp, std::ptr::null()) == 0 {
+            return Err(format!(
+                "WebP animation encoding failed: {}",
+                encoder.error()
+            ));
+        }
+
+        let mut data = WebPData::default();
+        if WebPAnimEncoderAssemble(encoder.0, &mut data) == 0 {
+            return Err(format!(
+                "WebP animation encoding failed: {}",
+                encoder.error()
+            ));
+        }
+        let bytes = std::slice::from_raw_parts(data.bytes, data.size).to_vec();
+        WebPDataClear(&mut data);
+        bytes
+    };
+
+    insert_exif(&webp_data, exif_bytes)
+}
+
+// libwebp animation encoder, deleted when dropped
+struct AnimEncoder(*mut WebPAnimEncoder);
+
+impl AnimEncoder {
+    // Last error message reported by libwebp
+    fn error(&self) -> String {
+        // SAFETY: encoder is valid, libwebp returns a NUL terminated string or null
+        unsafe {
+            let message = WebPAnimEncoderGetError(self.0);
+            if message.is_null() {
+                return "Unknown error".to_string();
+            }
+            std::ffi::CStr::from_ptr(message)
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+}
+
+impl Drop for AnimEncoder {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            // SAFETY: pointer came from WebPAnimEncoderNewInternal and is deleted once
+            unsafe { WebPAnimEncoderDelete(self.0) };
+        }
+    }
+}
+
+// libwebp config from quality and encoder options
+fn webp_config(quality: u8, options: &WebpOptions) -> Result<WebPConfig, String> {
+    // Preset tunes filtering/SNS for the content type, applied before explicit options
+    let mut config = match options.preset.as_deref() {
+        Some(name) => WebPConfig::new_with_preset(preset(name)?, quality as f32),
+        None => WebPConfig::new(),
+    }
+    .map_err(|_| "Failed to initialize WebP config".to_string())?;
+
+    // Lossless: quality controls compression effort instead of visual quality
+    config.quality = quality as f32;
+    config.lossless = options.is_lossless() as i32;
+    config.method = options.method.min(6) as i32;
+    config.alpha_quality = options.alpha_quality.min(100) as i32;
+    config.use_sharp_yuv = options.sharp_yuv as i32;
+    if let Some(level) = options.near_lossless {
+        // 0 = maximum preprocessing, 100 = off
+        config.near_lossless = level.min(100) as i32;
+    }
+
+    Ok(config)
+}
+
+// Insert EXIF data if provided
+fn insert_exif(webp_data: &[u8], exif_bytes: Option<&[u8]>) -> Result<Vec<u8>, String> {
     if let Some(exif) = exif_bytes {
-        let mut webp = WebP::from_bytes(Bytes::copy_from_slice(&webp_data))
+        let mut webp = WebP::from_bytes(Bytes::copy_from_slice(webp_data))
             .map_err(|e| format!("Failed to parse WebP: {}", e))?;
 
         webp.set_exif(Some(Bytes::copy_from_slice(exif)));
@@ -27,3 +176,16 @@ pub fn convert_to_webp(
 
     Ok(webp_data.to_vec())
 }
+
+// Map preset name to libwebp preset
+fn preset(name: &str) -> Result<WebPPreset, String> {
+    match name {
+        "default" => Ok(WebPPreset::WEBP_PRESET_DEFAULT),
+        "picture" => Ok(WebPPreset::WEBP_PRESET_PICTURE),
+        "photo" => Ok(WebPPreset::WEBP_PRESET_PHOTO),
+        "drawing" => Ok(WebPPreset::WEBP_PRESET_DRAWING),
+        "icon" => Ok(WebPPreset::WEBP_PRESET_ICON),
+        "text" => Ok(WebPPreset::WEBP_PRESET_TEXT),
+        _ => Err(format!("Unsupported WebP preset: {}", name)),
+    }
+}
@@ -0,0 +1,456 @@
+use std::io::{Cursor, Read};
+
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::{PhotometricInterpretation, Tag};
+use tiff::ColorType;
+
+use crate::converters::{Gray16Image, Rgb16Image, Rgba16Image};
+use crate::models::RawOptions;
+use crate::pdf::is_pdf;
+use crate::raw::{decode_raw, is_raw};
+
+// Decode a source file for conversion
+// Camera RAW goes first: the image crate would only read its small TIFF thumbnail
+pub fn decode_source(data: &[u8], raw_options: &RawOptions) -> Result<image::DynamicImage, String> {
+    if is_raw(data) {
+        return decode_raw(data, raw_options);
+    }
+    // PDFs are rasterized into page items when added (add_pdf_pages)
+    if is_pdf(data) {
+        return Err("PDF files must be added as pages".to_string());
+    }
+
+    decode_image(data)
+}
+
+// Decode every page of a source file: one image per page of a multi-page TIFF,
+// the decode_source image otherwise
+pub fn decode_pages(
+    data: &[u8],
+    raw_options: &RawOptions,
+) -> Result<Vec<image::DynamicImage>, String> {
+    // DNG and most RAW formats are TIFF based, their extra IFDs are not pages
+    if !is_raw(data) && tiff_page_count(data) > 1 {
+        return decode_tiff_pages(data);
+    }
+
+    decode_source(data, raw_options).map(|img| vec![img])
+}
+
+// Full resolution pages of a TIFF (0 for other formats)
+fn tiff_page_count(data: &[u8]) -> usize {
+    if image::guess_format(data).ok() != Some(image::ImageFormat::Tiff) {
+        return 0;
+    }
+    let Ok(mut decoder) = Decoder::new(Cursor::new(data)) else {
+        return 0;
+    };
+
+    let mut count = 0;
+    loop {
+        if !is_reduced_resolution(&mut decoder) {
+            count += 1;
+        }
+        if !decoder.more_images() || decoder.next_image().is_err() {
+            return count;
+        }
+    }
+}
+
+fn decode_tiff_pages(data: &[u8]) -> Result<Vec<image::DynamicImage>, String> {
+    let mut decoder =
+        Decoder::new(Cursor::new(data)).map_err(|e| format!("Failed to decode TIFF: {}", e))?;
+
+    let mut pages = Vec::new();
+    loop {
+        if !is_reduced_resolution(&mut decoder) {
+            let page = pages.len() + 1;
+            pages.push(
+                decode_tiff_page(data, &mut decoder)
+                    .map_err(|e| format!("Failed to decode TIFF page {}: {}", page, e))?,
+            );
+        }
+        if !decoder.more_images() {
+            return Ok(pages);
+        }
+        decoder
+            .next_image()
+            .map_err(|e| format!("Failed to decode TIFF: {}", e))?;
+    }
+}
+
+// Thumbnails are flagged in bit 0 of NewSubfileType
+fn is_reduced_resolution(decoder: &mut Decoder<Cursor<&[u8]>>) -> bool {
+    decoder
+        .find_tag_unsigned::<u32>(Tag::NewSubfileType)
+        .ok()
+        .flatten()
+        .is_some_and(|subfile_type| subfile_type & 1 != 0)
+}
+
+// Current page of the decoder at its native depth
+fn decode_tiff_page(
+    data: &[u8],
+    decoder: &mut Decoder<Cursor<&[u8]>>,
+) -> Result<image::DynamicImage, String> {
+    let photometric = decoder
+        .find_tag_unsigned::<u16>(Tag::PhotometricInterpretation)
+        .map_err(|e| e.to_string())?;
+    if photometric == Some(PhotometricInterpretation::RGBPalette.to_u16()) {
+        return decode_tiff_palette_page(data, decoder);
+    }
+
+    let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+    let color = decoder.colortype().map_err(|e| e.to_string())?;
+    let pixels = decoder.read_image().map_err(|e| e.to_string())?;
+
+    let img = match (color, pixels) {
+        // Bilevel and other packed gray pages, the tiff crate has already
+        // inverted WhiteIsZero samples so the highest value is white
+        (ColorType::Gray(bits @ (1 | 2 | 4)), DecodingResult::U8(pixels)) => {
+            let max = (1u16 << bits) - 1;
+            let gray = unpack_samples(&pixels, width, height, bits)?
+                .into_iter()
+                .map(|value| (u16::from(value) * 255 / max) as u8)
+                .collect();
+            image::GrayImage::from_raw(width, height, gray).map(image::DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(8), DecodingResult::U8(pixels)) => {
+            image::GrayImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(pixels)) => {
+            Gray16Image::from_raw(width, height, pixels).map(image::DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(pixels)) => {
+            image::ImageBuffer::from_raw(width, height, pixels)
+                .map(image::DynamicImage::ImageLumaA8)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(pixels)) => {
+            image::ImageBuffer::from_raw(width, height, pixels)
+                .map(image::DynamicImage::ImageLumaA16)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(pixels)) => {
+            image::RgbImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(pixels)) => {
+            Rgb16Image::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGB(32), DecodingResult::F32(pixels)) => {
+            image::Rgb32FImage::from_raw(width, height, pixels)
+                .map(image::DynamicImage::ImageRgb32F)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(pixels)) => {
+            image::RgbaImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgba8)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(pixels)) => {
+            Rgba16Image::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgba16)
+        }
+        (ColorType::RGBA(32), DecodingResult::F32(pixels)) => {
+            image::Rgba32FImage::from_raw(width, height, pixels)
+                .map(image::DynamicImage::ImageRgba32F)
+        }
+        // Naive conversion without a color profile, like print previews do
+        (ColorType::CMYK(8), DecodingResult::U8(pixels)) => {
+            let rgb = pixels
+                .chunks_exact(4)
+                .flat_map(|cmyk| {
+                    let white = 255 - cmyk[3] as u16;
+                    [0, 1, 2].map(|channel| ((255 - cmyk[channel] as u16) * white / 255) as u8)
+                })
+                .collect();
+            image::RgbImage::from_raw(width, height, rgb).map(image::DynamicImage::ImageRgb8)
+        }
+        (color, _) => return Err(format!("unsupported color type {:?}", color)),
+    };
+
+    img.ok_or_else(|| "pixel data does not match the page size".to_string())
+}
+
+// The tiff crate can't read palette pages: their strips are read directly and
+// the indices looked up in the ColorMap (all red values, then all green values,
+// then all blue values)
+fn decode_tiff_palette_page(
+    data: &[u8],
+    decoder: &mut Decoder<Cursor<&[u8]>>,
+) -> Result<image::DynamicImage, String> {
+    let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+    let bits = decoder
+        .find_tag_unsigned::<u8>(Tag::BitsPerSample)
+        .map_err(|e| e.to_string())?
+        .unwrap_or(1);
+    if !matches!(bits, 1 | 2 | 4 | 8) {
+        return Err(format!("unsupported palette bit depth {}", bits));
+    }
+    let colormap = decoder
+        .get_tag_u16_vec(Tag::ColorMap)
+        .map_err(|e| format!("missing color map: {}", e))?;
+    let entries = 1usize << bits;
+    if colormap.len() != entries * 3 {
+        return Err("color map does not match the bit depth".to_string());
+    }
+
+    let packed = read_strips(data, decoder)?;
+    let indices = if bits == 8 {
+        let pixels = width as usize * height as usize;
+        packed
+            .get(..pixels)
+            .ok_or_else(|| "pixel data does not match the page size".to_string())?
+            .to_vec()
+    } else {
+        unpack_samples(&packed, width, height, bits)?
+    };
+
+    let rgb = indices
+        .into_iter()
+        .flat_map(|index| {
+            [0, 1, 2].map(|channel| (colormap[channel * entries + index as usize] >> 8) as u8)
+        })
+        .collect();
+    image::RgbImage::from_raw(width, height, rgb)
+        .map(image::DynamicImage::ImageRgb8)
+        .ok_or_else(|| "pixel data does not match the page size".to_string())
+}
+
+// Decompressed strips of the current page, concatenated
+fn read_strips(data: &[u8], decoder: &mut Decoder<Cursor<&[u8]>>) -> Result<Vec<u8>, String> {
+    let mut find = |tag: Tag| {
+        decoder
+            .find_tag_unsigned::<u64>(tag)
+            .map_err(|e| e.to_string())
+    };
+    let compression = find(Tag::Compression)?.unwrap_or(1);
+    if find(Tag::Predictor)?.is_some_and(|predictor| predictor != 1) {
+        return Err("predictors are not supported for palette pages".to_string());
+    }
+
+    let offsets = decoder
+        .find_tag_unsigned_vec::<u64>(Tag::StripOffsets)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "tiled palette pages are not supported".to_string())?;
+    let counts = decoder
+        .get_tag_u64_vec(Tag::StripByteCounts)
+        .map_err(|e| e.to_string())?;
+
+    let mut pixels = Vec::new();
+    for (&offset, &count) in offsets.iter().zip(&counts) {
+        let strip = usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(count).ok())
+            .and_then(|(offset, count)| data.get(offset..offset.checked_add(count)?))
+            .ok_or_else(|| "strip is outside the file".to_string())?;
+
+        match compression {
+            1 => pixels.extend_from_slice(strip),
+            5 => pixels.extend(
+                weezl::decode::Decoder::with_tiff_size_switch(weezl::BitOrder::Msb, 8)
+                    .decode(strip)
+                    .map_err(|e| format!("LZW: {}", e))?,
+            ),
+            8 | 32946 => {
+                flate2::read::ZlibDecoder::new(strip)
+                    .read_to_end(&mut pixels)
+                    .map_err(|e| format!("Deflate: {}", e))?;
+            }
+            32773 => unpack_bits(strip, &mut pixels),
+            other => return Err(format!("unsupported palette compression {}", other)),
+        }
+    }
+
+    Ok(pixels)
+}
+
+// PackBits: header n < 128 copies the next n + 1 bytes,
+// n > 128 repeats the next byte 257 - n times, 128 is skipped
+fn unpack_bits(mut packed: &[u8], out: &mut Vec<u8>) {
+    while let Some((&header, rest)) = packed.split_first() {
+        packed = match header {
+            0..=127 => {
+                let (literal, rest) = rest.split_at((usize::from(header) + 1).min(rest.len()));
+                out.extend_from_slice(literal);
+                rest
+            }
+            129..=255 => {
+                let Some((&byte, rest)) = rest.split_first() else {
+                    break;
+                };
+                out.resize(out.len() + 257 - usize::from(header), byte);
+                rest
+            }
+            128 => rest,
+        };
+    }
+}
+
+// Samples of 1, 2 or 4 bit rows, every row starts on a byte boundary
+fn unpack_samples(packed: &[u8], width: u32, height: u32, bits: u8) -> Result<Vec<u8>, String> {
+    let width = width as usize;
+    let bits = bits as usize;
+    let row_bytes = (width * bits).div_ceil(8);
+    if packed.len() < row_bytes * height as usize {
+        return Err("pixel data does not match the page size".to_string());
+    }
+    if row_bytes == 0 {
+        return Ok(Vec::new());
+    }
+    let mask = (1u8 << bits) - 1;
+
+    Ok(packed
+        .chunks_exact(row_bytes)
+        .take(height as usize)
+        .flat_map(|row| {
+            (0..width).map(move |x| {
+                let bit = x * bits;
+                (row[bit / 8] >> (8 - bits - bit % 8)) & mask
+            })
+        })
+        .collect())
+}
+
+// Decode image bytes into DynamicImage
+// Tries image crate first, then JPEG XL, then falls back to AVIF and TGA decoding
+pub fn decode_image(data: &[u8]) -> Result<image::DynamicImage, String> {
+    let err = match image::load_from_memory(data) {
+        Ok(img) => return Ok(img),
+        Err(e) => e,
+    };
+
+    if is_jxl(data) {
+        return decode_jxl(data);
+    }
+
+    // If standard decoding fails, try AVIF decoding
+    // AVIF decode → RGBA/RGB pixels → DynamicImage → target format
+    let decoder = match avif_decode::Decoder::from_avif(data) {
+        Ok(decoder) => decoder,
+        // TGA has no signature to detect, it is the last format tried
+        Err(avif_err) => {
+            return image::load_from_memory_with_format(data, image::ImageFormat::Tga).map_err(
+                |_| {
+                    format!(
+                        "Failed to decode image: {} (AVIF decode also failed: {})",
+                        err, avif_err
+                    )
+                },
+            );
+        }
+    };
+
+    let avif_image = decoder
+        .to_image()
+        .map_err(|e| format!("Failed to convert AVIF to image: {}", e))?;
+
+    // Convert avif_decode::Image to image::DynamicImage
+    match avif_image {
+        avif_decode::Image::Rgba8(img) => {
+            // RGBA8 → Vec<u8>
+            let pixels: Vec<u8> = img
+                .buf()
+                .iter()
+                .flat_map(|px| [px.r, px.g, px.b, px.a])
+                .collect();
+            image::RgbaImage::from_raw(img.width() as u32, img.height() as u32, pixels)
+                .map(image::DynamicImage::ImageRgba8)
+                .ok_or_else(|| "Failed to create RGBA image from AVIF".to_string())
+        }
+        avif_decode::Image::Rgb8(img) => {
+            // RGB8 → Vec<u8>
+            let pixels: Vec<u8> = img.buf().iter().flat_map(|px| [px.r, px.g, px.b]).collect();
+            image::RgbImage::from_raw(img.width() as u32, img.height() as u32, pixels)
+                .map(image::DynamicImage::ImageRgb8)
+                .ok_or_else(|| "Failed to create RGB image from AVIF".to_string())
+        }
+        avif_decode::Image::Rgba16(img) => {
+            // RGBA16 kept at native depth, reduced later only if the target needs it
+            let pixels: Vec<u16> = img
+                .buf()
+                .iter()
+                .flat_map(|px| [px.r, px.g, px.b, px.a])
+                .collect();
+            Rgba16Image::from_raw(img.width() as u32, img.height() as u32, pixels)
+                .map(image::DynamicImage::ImageRgba16)
+                .ok_or_else(|| "Failed to create RGBA image from AVIF (16-bit)".to_string())
+        }
+        avif_decode::Image::Rgb16(img) => {
+            // RGB16 kept at native depth
+            let pixels: Vec<u16> = img.buf().iter().flat_map(|px| [px.r, px.g, px.b]).collect();
+            Rgb16Image::from_raw(img.width() as u32, img.height() as u32, pixels)
+                .map(image::DynamicImage::ImageRgb16)
+                .ok_or_else(|| "Failed to create RGB image from AVIF (16-bit)".to_string())
+        }
+        avif_decode::Image::Gray8(img) => {
+            // Gray8 → Luma8
+            let pixels: Vec<u8> = img.buf().iter().map(|px| px.value()).collect();
+            image::GrayImage::from_raw(img.width() as u32, img.height() as u32, pixels)
+                .map(image::DynamicImage::ImageLuma8)
+                .ok_or_else(|| "Failed to create Gray image from AVIF".to_string())
+        }
+        avif_decode::Image::Gray16(img) => {
+            // Gray16 → Luma16 (native depth)
+            let pixels: Vec<u16> = img.buf().iter().map(|px| px.value()).collect();
+            Gray16Image::from_raw(img.width() as u32, img.height() as u32, pixels)
+                .map(image::DynamicImage::ImageLuma16)
+                .ok_or_else(|| "Failed to create Gray image from AVIF (16-bit)".to_string())
+        }
+    }
+}
+
+// JPEG XL bare codestream or ISOBMFF container signature
+fn is_jxl(data: &[u8]) -> bool {
+    data.starts_with(&[0xFF, 0x0A])
+        || data.starts_with(&[
+            0x00, 0x00, 0x00, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+        ])
+}
+
+// Decode JPEG XL via libjxl
+// Samples come back at the source depth: 8-bit sources as u8, deeper ones as u16
+fn decode_jxl(data: &[u8]) -> Result<image::DynamicImage, String> {
+    use jpegxl_rs::decode::Pixels;
+
+    let decoder = jpegxl_rs::decoder_builder()
+        .build()
+        .map_err(|e| format!("JPEG XL decoder setup failed: {}", e))?;
+    let (metadata, pixels) = decoder
+        .decode(data)
+        .map_err(|e| format!("Failed to decode JPEG XL: {}", e))?;
+
+    // Float samples are clamped to 0-1 like libjxl's own integer output
+    let unit = |v: f32| (v.clamp(0.0, 1.0) * 65535.0).round() as u16;
+    let (width, height) = (metadata.width, metadata.height);
+    let img = match pixels {
+        Pixels::Uint8(pixels) => jxl_image8(width, height, pixels),
+        Pixels::Uint16(pixels) => jxl_image16(width, height, pixels),
+        Pixels::Float(pixels) => jxl_image16(width, height, pixels.into_iter().map(unit).collect()),
+        Pixels::Float16(pixels) => jxl_image16(
+            width,
+            height,
+            pixels.into_iter().map(|v| unit(v.to_f32())).collect(),
+        ),
+    };
+
+    img.ok_or_else(|| "Failed to create image from JPEG XL".to_string())
+}
+
+// Interleaved 8-bit JPEG XL samples, the channel count follows from the buffer size
+fn jxl_image8(width: u32, height: u32, pixels: Vec<u8>) -> Option<image::DynamicImage> {
+    match pixels.len() / (width as usize * height as usize).max(1) {
+        1 => image::GrayImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageLuma8),
+        2 => image::GrayAlphaImage::from_raw(width, height, pixels)
+            .map(image::DynamicImage::ImageLumaA8),
+        3 => image::RgbImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgb8),
+        4 => image::RgbaImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgba8),
+        _ => None,
+    }
+}
+
+// Interleaved 16-bit JPEG XL samples
+fn jxl_image16(width: u32, height: u32, pixels: Vec<u16>) -> Option<image::DynamicImage> {
+    match pixels.len() / (width as usize * height as usize).max(1) {
+        1 => Gray16Image::from_raw(width, height, pixels).map(image::DynamicImage::ImageLuma16),
+        2 => image::ImageBuffer::from_raw(width, height, pixels)
+            .map(image::DynamicImage::ImageLumaA16),
+        3 => Rgb16Image::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgb16),
+        4 => Rgba16Image::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgba16),
+        _ => None,
+    }
+}
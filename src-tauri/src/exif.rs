@@ -6,13 +6,20 @@ use img_parts::webp::WebP;
 use img_parts::{Bytes, ImageEXIF};
 
 use crate::models::ExifData;
+use crate::raw::is_raw;
 
 // Helper function to extract EXIF data from image bytes
 pub fn extract_exif_from_bytes(data: &[u8]) -> Option<ExifData> {
     let mut cursor = Cursor::new(data);
     let exif_reader = exif::Reader::new();
 
-    match exif_reader.read_from_container(&mut cursor) {
+    // kamadak-exif doesn't read JPEG XL containers, parse the Exif box payload instead
+    let exif = match jxl_exif(data) {
+        Some(raw) => exif_reader.read_raw(raw),
+        None => exif_reader.read_from_container(&mut cursor),
+    };
+
+    match exif {
         Ok(exif) => {
             let mut exif_data = ExifData {
                 date_time: None,
@@ -132,5 +139,187 @@ pub fn extract_exif_raw_bytes(data: &[u8]) -> Option<Vec<u8>> {
         }
     }
 
+    // Try JPEG XL
+    if let Some(exif) = jxl_exif(data) {
+        return Some(exif);
+    }
+
+    // Try camera RAW
+    if is_raw(data) {
+        return raw_exif(data);
+    }
+
+    None
+}
+
+// Tags copied from the main IFD of a RAW file, the rest describes the sensor data
+const RAW_TIFF_TAGS: [exif::Tag; 8] = [
+    exif::Tag::Make,
+    exif::Tag::Model,
+    exif::Tag::Orientation,
+    exif::Tag::DateTime,
+    exif::Tag::Software,
+    exif::Tag::Artist,
+    exif::Tag::Copyright,
+    exif::Tag::ImageDescription,
+];
+
+// RAW files are TIFF containers: rebuild a standalone EXIF block from the
+// primary image's camera, Exif and GPS fields (maker notes are dropped)
+fn raw_exif(data: &[u8]) -> Option<Vec<u8>> {
+    let exif = exif::Reader::new().read_raw(data.to_vec()).ok()?;
+
+    let mut writer = exif::experimental::Writer::new();
+    for field in exif.fields() {
+        if field.ifd_num != exif::In::PRIMARY
+            || field.tag == exif::Tag::MakerNote
+            || matches!(field.value, exif::Value::Unknown(..))
+        {
+            continue;
+        }
+        let keep = match field.tag.context() {
+            exif::Context::Exif | exif::Context::Gps => true,
+            exif::Context::Tiff => RAW_TIFF_TAGS.contains(&field.tag),
+            _ => false,
+        };
+        if keep {
+            writer.push_field(field);
+        }
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    writer.write(&mut buffer, exif.little_endian()).ok()?;
+    Some(buffer.into_inner())
+}
+
+// TIFF data from the Exif box of a JPEG XL container (brotli compressed brob boxes are skipped)
+fn jxl_exif(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0, 0, 0, 0x0C, b'J', b'X', b'L', b' ']) {
+        return None;
+    }
+
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let size = u32::from_be_bytes(data[offset..offset + 4].try_into().ok()?) as usize;
+        let box_type = &data[offset + 4..offset + 8];
+
+        // size 1 = 64-bit size follows, size 0 = box extends to end of file
+        let (header, size) = match size {
+            0 => (8, data.len() - offset),
+            1 => {
+                let large = data.get(offset + 8..offset + 16)?;
+                (16, u64::from_be_bytes(large.try_into().ok()?) as usize)
+            }
+            size => (8, size),
+        };
+        let end = offset.checked_add(size)?.min(data.len());
+        if size < header {
+            return None;
+        }
+
+        if box_type == b"Exif" {
+            // Payload starts with the offset to the TIFF header
+            let payload = data.get(offset + header..end)?;
+            let tiff_offset = u32::from_be_bytes(payload.get(0..4)?.try_into().ok()?) as usize;
+            return payload.get(4 + tiff_offset..).map(|tiff| tiff.to_vec());
+        }
+
+        offset = end;
+    }
+
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURE: [u8; 12] = [
+        0, 0, 0, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+    ];
+    const TIFF: [u8; 8] = [b'I', b'I', 42, 0, 8, 0, 0, 0];
+
+    fn jxl_box(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(box_type);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    // Exif box payload: offset to the TIFF header, then the TIFF data
+    fn exif_payload(skip: &[u8]) -> Vec<u8> {
+        [&(skip.len() as u32).to_be_bytes()[..], skip, &TIFF].concat()
+    }
+
+    fn container(boxes: &[Vec<u8>]) -> Vec<u8> {
+        [&SIGNATURE[..], &boxes.concat()].concat()
+    }
+
+    #[test]
+    fn reads_exif_box_after_other_boxes() {
+        let data = container(&[
+            jxl_box(b"ftyp", b"jxl \0\0\0\0jxl "),
+            jxl_box(b"Exif", &exif_payload(&[])),
+            jxl_box(b"jxlc", &[0xFF, 0x0A]),
+        ]);
+        assert_eq!(jxl_exif(&data).unwrap(), TIFF);
+    }
+
+    #[test]
+    fn skips_to_tiff_header_offset() {
+        let data = container(&[jxl_box(b"Exif", &exif_payload(b"Exif\0\0"))]);
+        assert_eq!(jxl_exif(&data).unwrap(), TIFF);
+    }
+
+    #[test]
+    fn reads_large_and_open_ended_boxes() {
+        // size 1: 64-bit size after the type
+        let payload = exif_payload(&[]);
+        let mut large = 1u32.to_be_bytes().to_vec();
+        large.extend_from_slice(b"Exif");
+        large.extend_from_slice(&((payload.len() + 16) as u64).to_be_bytes());
+        large.extend_from_slice(&payload);
+        assert_eq!(jxl_exif(&container(&[large])).unwrap(), TIFF);
+
+        // size 0: box runs to the end of the file
+        let mut open = 0u32.to_be_bytes().to_vec();
+        open.extend_from_slice(b"Exif");
+        open.extend_from_slice(&payload);
+        assert_eq!(jxl_exif(&container(&[open])).unwrap(), TIFF);
+    }
+
+    #[test]
+    fn ignores_other_files_and_missing_exif() {
+        assert_eq!(jxl_exif(&[0xFF, 0x0A, 0, 0]), None);
+        assert_eq!(jxl_exif(&[]), None);
+        assert_eq!(
+            jxl_exif(&container(&[jxl_box(b"jxlc", &[0xFF, 0x0A])])),
+            None
+        );
+        // Brotli compressed Exif is not unpacked
+        assert_eq!(jxl_exif(&container(&[jxl_box(b"brob", b"Exif....")])), None);
+    }
+
+    #[test]
+    fn rejects_truncated_boxes() {
+        // Header cut short
+        assert_eq!(jxl_exif(&container(&[vec![0, 0, 0, 20, b'E']])), None);
+        // 64-bit size missing
+        assert_eq!(
+            jxl_exif(&container(&[vec![0, 0, 0, 1, b'E', b'x', b'i', b'f', 0]])),
+            None
+        );
+        // Declared size smaller than the header
+        assert_eq!(
+            jxl_exif(&container(&[vec![0, 0, 0, 4, b'E', b'x', b'i', b'f']])),
+            None
+        );
+        // Payload without the TIFF offset
+        assert_eq!(jxl_exif(&container(&[jxl_box(b"Exif", &[0, 0])])), None);
+        // TIFF offset past the end of the box
+        assert_eq!(
+            jxl_exif(&container(&[jxl_box(b"Exif", &[0, 0, 0, 64, 1, 2])])),
+            None
+        );
+    }
+}
@@ -1,29 +1,53 @@
+// Conversion pipeline modules are public for the integration tests
+pub mod animation;
 mod commands;
-mod converters;
+pub mod converters;
+pub mod decode;
 mod exif;
-mod models;
+pub mod metrics;
+pub mod models;
+mod pdf;
+mod raw;
+mod srcset;
 mod state;
+mod video;
 
-use state::FileListState;
+use state::{FileListState, VideoState};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .manage(FileListState(Arc::new(Mutex::new(Vec::new()))))
+        .manage(VideoState {
+            ffmpeg_path: Arc::new(Mutex::new(None)),
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+        })
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .invoke_handler(tauri::generate_handler![
             commands::add_file_from_path,
             commands::add_file_from_url,
+            commands::add_pdf_pages,
             commands::remove_file,
             commands::clear_files,
             commands::remove_converted_files,
+            commands::requeue_low_quality_files,
             commands::get_file_list,
             commands::save_file,
             commands::get_cpu_count,
-            commands::convert_images
+            commands::preview_conversion,
+            commands::convert_images,
+            commands::generate_favicon_set,
+            commands::combine_files,
+            commands::set_ffmpeg_path,
+            commands::get_ffmpeg_path,
+            commands::probe_video_file,
+            commands::transcode_video,
+            commands::extract_video_frames,
+            commands::cancel_video_conversion
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
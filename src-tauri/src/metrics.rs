@@ -0,0 +1,289 @@
+use image::DynamicImage;
+
+use crate::converters::{flatten_for_target, tone_map, tone_map_for_target, EncodedImage};
+use crate::decode::decode_image;
+use crate::models::{ConversionOptions, QualityReport};
+
+// SSIM Gaussian window (11x11, sigma 1.5 as in the SSIM paper), centered every STRIDE pixels
+const WINDOW_RADIUS: usize = 5;
+const WINDOW_SIGMA: f64 = 1.5;
+const STRIDE: usize = 4;
+
+// Stabilizing constants for channel values in 0.0 - 1.0 range
+const C1: f64 = 0.01 * 0.01;
+const C2: f64 = 0.03 * 0.03;
+
+// PSNR reported for identical images (MSE = 0 would be infinite)
+const MAX_PSNR: f64 = 100.0;
+
+// Decode encoded output and compare it against the source image
+// Files with SSIM below min_ssim are flagged for re-conversion
+pub fn compare_quality(
+    source: &DynamicImage,
+    encoded: &EncodedImage,
+    options: &ConversionOptions,
+    min_ssim: Option<f64>,
+) -> Result<QualityReport, String> {
+    let decoded = decode_image(&encoded.data)?;
+
+    // Compare against the same tone mapped / flattened source the encoder saw
+    let tone_mapped = tone_map_for_target(source, &options.target_format);
+    let source = tone_mapped.as_ref().unwrap_or(source);
+    let flattened = flatten_for_target(source, options);
+    let source = flattened.as_ref().unwrap_or(source);
+
+    // Float images left for HDR / OpenEXR targets are compared as displayed
+    let displayed = tone_map(source);
+    let source = displayed.as_ref().unwrap_or(source);
+    let decoded = tone_map(&decoded).unwrap_or(decoded);
+
+    // Compare against a source resized the same way when output was downscaled
+    let resized;
+    let reference = match encoded.resized_to {
+        Some((width, height)) => {
+            resized = source.resize_exact(width, height, image::imageops::FilterType::Lanczos3);
+            &resized
+        }
+        None => source,
+    };
+
+    let psnr = psnr(reference, &decoded)?;
+    let ssim = ssim(reference, &decoded)?;
+
+    Ok(QualityReport {
+        psnr,
+        ssim,
+        below_threshold: min_ssim.is_some_and(|min| ssim < min),
+    })
+}
+
+// Peak signal-to-noise ratio in dB over RGB channels (higher = closer to source)
+pub fn psnr(reference: &DynamicImage, distorted: &DynamicImage) -> Result<f64, String> {
+    if reference.width() != distorted.width() || reference.height() != distorted.height() {
+        return Err(format!(
+            "Cannot compare images of different sizes ({}x{} vs {}x{})",
+            reference.width(),
+            reference.height(),
+            distorted.width(),
+            distorted.height()
+        ));
+    }
+
+    let a = reference.to_rgb8();
+    let b = distorted.to_rgb8();
+    if a.as_raw().is_empty() {
+        return Err("Image is empty".to_string());
+    }
+
+    let squared_error: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&pa, &pb)| {
+            let diff = pa as f64 - pb as f64;
+            diff * diff
+        })
+        .sum();
+    let mse = squared_error / a.as_raw().len() as f64;
+
+    if mse == 0.0 {
+        return Ok(MAX_PSNR);
+    }
+
+    Ok((10.0 * (255.0 * 255.0 / mse).log10()).min(MAX_PSNR))
+}
+
+// Structural similarity between two images of equal size (1.0 = identical)
+// Mean of the R, G and B channel SSIM (chroma loss counts, not only luma),
+// each averaged over Gaussian weighted windows
+pub fn ssim(reference: &DynamicImage, distorted: &DynamicImage) -> Result<f64, String> {
+    if reference.width() != distorted.width() || reference.height() != distorted.height() {
+        return Err(format!(
+            "Cannot compare images of different sizes ({}x{} vs {}x{})",
+            reference.width(),
+            reference.height(),
+            distorted.width(),
+            distorted.height()
+        ));
+    }
+
+    let a = reference.to_rgb8();
+    let b = distorted.to_rgb8();
+    let (width, height) = (a.width() as usize, a.height() as usize);
+    if width == 0 || height == 0 {
+        return Err("Image is empty".to_string());
+    }
+
+    let kernel: Vec<f64> = (0..=2 * WINDOW_RADIUS)
+        .map(|i| {
+            let d = i as f64 - WINDOW_RADIUS as f64;
+            (-d * d / (2.0 * WINDOW_SIGMA * WINDOW_SIGMA)).exp()
+        })
+        .collect();
+    let centers_x: Vec<usize> = (0..width).step_by(STRIDE).collect();
+
+    // Horizontal pass at the window centers for the rows the current windows cover
+    // (ring buffer indexed by y), vertical pass on top of it
+    let mut rows = vec![vec![WindowSums::default(); centers_x.len()]; kernel.len()];
+    let mut next_row = 0;
+    let mut total = 0.0;
+    let mut count = 0u64;
+    for cy in (0..height).step_by(STRIDE) {
+        while next_row <= (cy + WINDOW_RADIUS).min(height - 1) {
+            let row = &mut rows[next_row % kernel.len()];
+            let a_row = &a.as_raw()[next_row * width * 3..(next_row + 1) * width * 3];
+            let b_row = &b.as_raw()[next_row * width * 3..(next_row + 1) * width * 3];
+            for (sums, &cx) in row.iter_mut().zip(&centers_x) {
+                *sums = WindowSums::default();
+                for (x, &w) in window_taps(cx, width, &kernel) {
+                    sums.add_pixels(w, &a_row[x * 3..x * 3 + 3], &b_row[x * 3..x * 3 + 3]);
+                }
+            }
+            next_row += 1;
+        }
+
+        total += (0..centers_x.len())
+            .map(|i| {
+                let mut sums = WindowSums::default();
+                for (y, &w) in window_taps(cy, height, &kernel) {
+                    sums.add_scaled(w, &rows[y % kernel.len()][i]);
+                }
+                sums.ssim()
+            })
+            .sum::<f64>();
+        count += centers_x.len() as u64;
+    }
+
+    Ok(total / count as f64)
+}
+
+// Kernel taps of the window centered at `center`, cut off at the image edges
+fn window_taps(
+    center: usize,
+    size: usize,
+    kernel: &[f64],
+) -> impl Iterator<Item = (usize, &f64)> + '_ {
+    kernel
+        .iter()
+        .enumerate()
+        .filter_map(move |(k, w)| Some(((center + k).checked_sub(WINDOW_RADIUS)?, w)))
+        .filter(move |&(position, _)| position < size)
+}
+
+// Weighted sums of a, b, a², b² and ab per channel (values in 0.0 - 1.0) and the total weight
+#[derive(Clone, Copy, Default)]
+struct WindowSums {
+    channels: [[f64; 5]; 3],
+    weight: f64,
+}
+
+impl WindowSums {
+    fn add_pixels(&mut self, w: f64, a: &[u8], b: &[u8]) {
+        for ((sums, &pa), &pb) in self.channels.iter_mut().zip(a).zip(b) {
+            let (pa, pb) = (pa as f64 / 255.0, pb as f64 / 255.0);
+            let values = [pa, pb, pa * pa, pb * pb, pa * pb];
+            for (sum, value) in sums.iter_mut().zip(values) {
+                *sum += w * value;
+            }
+        }
+        self.weight += w;
+    }
+
+    fn add_scaled(&mut self, w: f64, other: &WindowSums) {
+        for (sums, other) in self.channels.iter_mut().zip(&other.channels) {
+            for (sum, value) in sums.iter_mut().zip(other) {
+                *sum += w * value;
+            }
+        }
+        self.weight += w * other.weight;
+    }
+
+    // Mean SSIM of the three channels
+    fn ssim(&self) -> f64 {
+        let n = self.weight;
+        self.channels
+            .iter()
+            .map(|&[sum_a, sum_b, sum_aa, sum_bb, sum_ab]| {
+                let mean_a = sum_a / n;
+                let mean_b = sum_b / n;
+                let var_a = sum_aa / n - mean_a * mean_a;
+                let var_b = sum_bb / n - mean_b * mean_b;
+                let covar = sum_ab / n - mean_a * mean_b;
+
+                ((2.0 * mean_a * mean_b + C1) * (2.0 * covar + C2))
+                    / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2))
+            })
+            .sum::<f64>()
+            / 3.0
+    }
+}
+
+// Structural dissimilarity (0.0 = identical, higher = worse), DSSIM = 1 / SSIM - 1
+// Same formula as the dssim tool, but from the single scale RGB SSIM above,
+// so thresholds are not interchangeable with its multi-scale Lab scores
+pub fn dssim(reference: &DynamicImage, distorted: &DynamicImage) -> Result<f64, String> {
+    let ssim = ssim(reference, distorted)?;
+    Ok(1.0 / ssim.max(f64::EPSILON) - 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn solid(width: u32, height: u32, color: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb(color)))
+    }
+
+    #[test]
+    fn identical_images_are_perfect() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(40, 30, |x, y| {
+            Rgb([(x * 6) as u8, (y * 8) as u8, ((x + y) * 3) as u8])
+        }));
+        assert_eq!(psnr(&img, &img).unwrap(), MAX_PSNR);
+        assert!((ssim(&img, &img).unwrap() - 1.0).abs() < 1e-9);
+        assert!(dssim(&img, &img).unwrap().abs() < 1e-9);
+    }
+
+    #[test]
+    fn psnr_of_off_by_one_image() {
+        // MSE 1 -> 10 * log10(255²)
+        let value = psnr(&solid(8, 8, [10, 10, 10]), &solid(8, 8, [11, 11, 11])).unwrap();
+        assert!((value - 48.1308).abs() < 1e-3, "{}", value);
+    }
+
+    #[test]
+    fn single_pixel_images_compare() {
+        let black = solid(1, 1, [0, 0, 0]);
+        assert!((ssim(&black, &black).unwrap() - 1.0).abs() < 1e-9);
+        assert!(ssim(&black, &solid(1, 1, [255, 255, 255])).unwrap() < 0.01);
+    }
+
+    #[test]
+    fn chroma_loss_lowers_ssim() {
+        // Red / green stripes of equal luma, distorted to their average color
+        // (what chroma subsampling does to fine color detail)
+        let stripes = DynamicImage::ImageRgb8(RgbImage::from_fn(32, 32, |x, _| {
+            if x % 2 == 0 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 76, 0])
+            }
+        }));
+        let averaged = solid(32, 32, [128, 38, 0]);
+        let luma = |img: &DynamicImage, x| img.to_luma8().get_pixel(x, 0).0[0];
+        assert_eq!(luma(&stripes, 0), luma(&stripes, 1));
+        assert_eq!(luma(&stripes, 0), luma(&averaged, 0));
+
+        assert!(ssim(&stripes, &averaged).unwrap() < 0.5);
+        assert!(dssim(&stripes, &averaged).unwrap() > 1.0);
+    }
+
+    #[test]
+    fn rejects_mismatched_and_empty_images() {
+        assert!(psnr(&solid(4, 4, [0; 3]), &solid(4, 5, [0; 3])).is_err());
+        assert!(ssim(&solid(4, 4, [0; 3]), &solid(5, 4, [0; 3])).is_err());
+        assert!(psnr(&solid(0, 0, [0; 3]), &solid(0, 0, [0; 3])).is_err());
+        assert!(ssim(&solid(0, 0, [0; 3]), &solid(0, 0, [0; 3])).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // EXIF metadata structure
 #[derive(Serialize, Clone)]
@@ -38,6 +38,9 @@ pub struct FileItem {
     pub timestamps: Option<FileTimestamps>,
     pub converted: bool,
     pub converted_path: Option<String>, // Path where converted file was saved
+    pub output_paths: Vec<String>,      // Every file written for it (output specs, pages)
+    pub below_quality_threshold: bool,  // Output SSIM fell below requested minimum
+    pub frame_count: u32,               // Animation frames (1 for still images)
 }
 
 // Response type for frontend (no image bytes)
@@ -52,6 +55,8 @@ pub struct FileItemResponse {
     pub exif: Option<ExifData>,
     pub converted: bool,
     pub converted_path: Option<String>, // Path where converted file was saved
+    pub below_quality_threshold: bool,  // Output SSIM fell below requested minimum
+    pub frame_count: u32,               // Animation frames (1 for still images)
 }
 
 impl FileItem {
@@ -66,6 +71,8 @@ impl FileItem {
             exif: self.exif.clone(),
             converted: self.converted,
             converted_path: self.converted_path.clone(),
+            below_quality_threshold: self.below_quality_threshold,
+            frame_count: self.frame_count,
         }
     }
 }
@@ -78,6 +85,411 @@ pub struct ConversionResult {
     pub original_size: u64,
     pub converted_size: u64,
     pub saved_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chosen_quality: Option<u8>, // Quality picked in target size / perceptual mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resized_to: Option<(u32, u32)>, // Output dimensions when downscaled to reach target size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dssim: Option<f64>, // Achieved DSSIM in perceptual target mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality_report: Option<QualityReport>, // PSNR / SSIM against source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub srcset: Option<SrcsetResult>, // Responsive image set written in srcset mode
+}
+
+// Responsive image set: <picture> snippet and manifest of every written image
+#[derive(Serialize)]
+pub struct SrcsetResult {
+    pub html: String,
+    pub sizes: String,
+    pub manifest_path: String, // This result as JSON, written next to the images
+    pub images: Vec<SrcsetImage>,
+}
+
+#[derive(Serialize)]
+pub struct SrcsetImage {
+    pub file_name: String,
+    pub saved_path: String,
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub size: u64, // Bytes
+}
+
+// Objective quality comparison between source and converted output
+#[derive(Serialize, Clone)]
+pub struct QualityReport {
+    pub psnr: f64,             // dB, higher = closer to source
+    pub ssim: f64,             // 0.0 - 1.0, 1.0 = identical (mean of R, G, B)
+    pub below_threshold: bool, // SSIM below requested minimum (re-run at higher quality)
+}
+
+// Encoding options shared by batch conversion and preview
+#[derive(Deserialize, Clone)]
+pub struct ConversionOptions {
+    pub target_format: String,
+    pub quality: u8,
+    pub avif_speed: u8,
+    #[serde(default)]
+    pub preserve_exif: bool,
+    #[serde(default)]
+    pub max_file_size: Option<u64>, // Target file size mode (bytes), quality becomes upper bound
+    #[serde(default)]
+    pub allow_downscale: bool, // Downscale when even minimum quality exceeds max_file_size
+    // Perceptual target: lowest quality with DSSIM at or below this
+    // (1 / SSIM - 1 of metrics::ssim, not comparable to dssim tool scores)
+    #[serde(default)]
+    pub max_dssim: Option<f64>,
+    #[serde(default)]
+    pub png: PngOptions,
+    #[serde(default)]
+    pub webp: WebpOptions,
+    #[serde(default)]
+    pub jpeg: JpegOptions,
+    #[serde(default)]
+    pub avif: AvifOptions,
+    #[serde(default)]
+    pub gif: GifOptions,
+    #[serde(default)]
+    pub jxl: JxlOptions,
+    #[serde(default)]
+    pub raw: RawOptions,
+    #[serde(default)]
+    pub icon: IconOptions,
+    #[serde(default)]
+    pub tiff: TiffOptions,
+    #[serde(default = "default_background")]
+    pub background: [u8; 3], // RGB used to flatten transparent pixels
+    #[serde(default)]
+    pub flatten_alpha: bool, // Flatten for every target, not only formats without alpha
+    #[serde(default)]
+    pub first_frame_only: bool, // Convert only the first frame of animated sources
+}
+
+fn default_background() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+// One output of a multi-output conversion: complete encoding options and a file name
+#[derive(Deserialize, Clone)]
+pub struct OutputSpec {
+    #[serde(flatten)]
+    pub options: ConversionOptions,
+    #[serde(default = "default_name_template")]
+    pub name: String, // File name template, {stem} = source file stem, {ext} = target format
+}
+
+pub fn default_name_template() -> String {
+    "{stem}.{ext}".to_string()
+}
+
+// PNG encoder options (compression level comes from quality, 0-9)
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PngOptions {
+    pub filter: String,     // "adaptive" | "none" | "sub" | "up" | "avg" | "paeth"
+    pub reduce_color: bool, // Drop alpha / use grayscale / palette when lossless
+    pub optimize: bool,     // Extra oxipng optimization pass
+    pub optimize_level: u8, // oxipng preset (0-6, higher = slower and smaller)
+    pub quantize: bool,     // Lossy 8-bit palette with alpha (still images only)
+    pub max_colors: u16,    // Palette size limit in quantize mode, 2-256
+    pub quality_min: u8,    // 0-100, skip the file when the palette cannot reach this
+    pub quality_max: u8,    // 0-100, fewest colors that reach this are used
+    pub dither_level: f32,  // 0.0 (none) - 1.0 (full Floyd-Steinberg)
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions {
+            filter: "adaptive".to_string(),
+            reduce_color: true,
+            optimize: false,
+            optimize_level: 2,
+            quantize: false,
+            max_colors: 256,
+            quality_min: 0,
+            quality_max: 100,
+            dither_level: 1.0,
+        }
+    }
+}
+
+// Preview result for a single in-memory conversion
+#[derive(Serialize)]
+pub struct ConversionPreview {
+    pub data: Vec<u8>, // Encoded bytes (downscaled when max_preview_size is given)
+    pub width: u32,
+    pub height: u32,
+    pub original_size: u64,
+    pub converted_size: u64,    // Size of full resolution output
+    pub compression_ratio: f64, // converted_size / original_size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chosen_quality: Option<u8>, // Quality picked in target size / perceptual mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dssim: Option<f64>, // Achieved DSSIM in perceptual target mode
+}
+
+// WebP encoder options (libwebp advanced config)
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct WebpOptions {
+    pub lossless: bool,
+    pub near_lossless: Option<u8>, // 0-100 (100 = off), implies lossless
+    pub method: u8,                // 0-6, higher = slower and smaller
+    pub alpha_quality: u8,         // 0-100
+    pub sharp_yuv: bool,           // Sharper RGB->YUV conversion (lossy only)
+    pub preset: Option<String>,    // "default" | "picture" | "photo" | "drawing" | "icon" | "text"
+}
+
+impl WebpOptions {
+    pub fn is_lossless(&self) -> bool {
+        self.lossless || self.near_lossless.is_some()
+    }
+}
+
+impl Default for WebpOptions {
+    fn default() -> Self {
+        WebpOptions {
+            lossless: false,
+            near_lossless: None,
+            method: 4,
+            alpha_quality: 100,
+            sharp_yuv: false,
+            preset: None,
+        }
+    }
+}
+
+// JPEG encoder options (mozjpeg)
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct JpegOptions {
+    pub progressive: bool,
+    pub chroma_subsampling: String, // "444" | "422" | "420"
+    pub optimize_huffman: bool,     // Optimized Huffman tables
+    pub trellis: bool,              // Trellis quantization (smaller files, slower)
+}
+
+impl Default for JpegOptions {
+    fn default() -> Self {
+        JpegOptions {
+            progressive: true,
+            chroma_subsampling: "420".to_string(),
+            optimize_huffman: true,
+            trellis: true,
+        }
+    }
+}
+
+// AVIF encoder options (ravif, rav1e for 4:2:0)
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct AvifOptions {
+    pub bit_depth: u8,              // 8 | 10
+    pub chroma_subsampling: String, // "444" | "420"
+    pub alpha_quality: Option<u8>,  // 1-100, defaults to color quality
+    pub alpha_color_mode: String,   // "clean" | "dirty" | "premultiplied"
+    pub color_model: String,        // "ycbcr" | "rgb" (rgb requires 444)
+}
+
+impl Default for AvifOptions {
+    fn default() -> Self {
+        AvifOptions {
+            bit_depth: 10,
+            chroma_subsampling: "444".to_string(),
+            alpha_quality: None,
+            alpha_color_mode: "clean".to_string(),
+            color_model: "ycbcr".to_string(),
+        }
+    }
+}
+
+// GIF encoder options (palette quantization)
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct GifOptions {
+    pub colors: u16,         // Palette size, 2-256 (one entry is used for transparency)
+    pub dithering: String,   // "floyd-steinberg" | "ordered" | "none"
+    pub alpha_threshold: u8, // Alpha below this becomes transparent (0 = always opaque)
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        GifOptions {
+            colors: 256,
+            dithering: "floyd-steinberg".to_string(),
+            alpha_threshold: 128,
+        }
+    }
+}
+
+// JPEG XL encoder options (libjxl)
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct JxlOptions {
+    pub mode: String, // "lossy" | "lossless" | "jpeg" (lossless recompression of JPEG sources)
+    pub effort: u8,   // 1-10, higher = slower and smaller
+}
+
+impl Default for JxlOptions {
+    fn default() -> Self {
+        JxlOptions {
+            mode: "lossy".to_string(),
+            effort: 7,
+        }
+    }
+}
+
+// Camera RAW decoding options
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RawOptions {
+    pub fast: bool, // Use the embedded JPEG preview instead of developing sensor data
+    pub white_balance: String, // "camera" (as shot) | "none" (raw sensor balance)
+    pub exposure: f32, // Exposure compensation in EV
+}
+
+impl Default for RawOptions {
+    fn default() -> Self {
+        RawOptions {
+            fast: false,
+            white_balance: "camera".to_string(),
+            exposure: 0.0,
+        }
+    }
+}
+
+// ICO / ICNS icon options
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct IconOptions {
+    pub sizes: Vec<u32>, // Square sizes, 16-1024 (ICO up to 256), empty = format defaults
+    pub png_min_size: u32, // ICO entries this size and larger are PNG, smaller ones BMP
+}
+
+impl Default for IconOptions {
+    fn default() -> Self {
+        IconOptions {
+            sizes: Vec::new(),
+            png_min_size: 256,
+        }
+    }
+}
+
+// TIFF encoder options
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct TiffOptions {
+    pub compression: String, // "none", "lzw", "deflate" or "packbits"
+    pub predictor: bool,     // Horizontal differencing before LZW / Deflate
+    pub bit_depth: u8,       // 8 or 16 bits per channel, 0 = follow the source
+    pub dpi: u32,            // Resolution tags, 0 = not written
+}
+
+impl Default for TiffOptions {
+    fn default() -> Self {
+        TiffOptions {
+            compression: "lzw".to_string(),
+            predictor: true,
+            bit_depth: 0,
+            dpi: 0,
+        }
+    }
+}
+
+// Favicon / PWA icon set options
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct FaviconOptions {
+    pub name: String,              // Manifest name, empty = source file stem
+    pub short_name: String,        // Manifest short_name, empty = name
+    pub theme_color: [u8; 3],      // RGB
+    pub background_color: [u8; 3], // RGB, also fills apple-touch and maskable icons
+    pub maskable_padding: f32,     // Padding per side of the maskable icon (fraction of size)
+}
+
+impl Default for FaviconOptions {
+    fn default() -> Self {
+        FaviconOptions {
+            name: String::new(),
+            short_name: String::new(),
+            theme_color: [255, 255, 255],
+            background_color: [255, 255, 255],
+            // Safe zone is the central 80%
+            maskable_padding: 0.1,
+        }
+    }
+}
+
+// Responsive image set (srcset) mode: every width in every format per file
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct SrcsetOptions {
+    pub widths: Vec<u32>,     // Output widths, capped to the source width
+    pub formats: Vec<String>, // Target formats, the last one is the <img> fallback
+    pub sizes: String,        // sizes attribute of the snippet
+    pub url_prefix: String,   // Prepended to file names in the snippet (e.g. "/images/")
+}
+
+impl Default for SrcsetOptions {
+    fn default() -> Self {
+        SrcsetOptions {
+            widths: vec![320, 640, 1280, 1920],
+            formats: vec!["avif".to_string(), "webp".to_string(), "jpeg".to_string()],
+            sizes: "100vw".to_string(),
+            url_prefix: String::new(),
+        }
+    }
+}
+
+// PDF rasterization options (pages become separate file items)
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PdfOptions {
+    pub dpi: u32,         // Render resolution, 1-1200
+    pub pages: Vec<u16>,  // 1-based page numbers, empty = every page
+    pub password: String, // For encrypted documents, empty = none
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            dpi: 150,
+            pages: Vec::new(),
+            password: String::new(),
+        }
+    }
+}
+
+// Combine mode: the selected files, in list order, become the pages of one PDF or TIFF
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CombineOptions {
+    pub format: String,      // "pdf" or "tiff"
+    pub file_name: String,   // Output name without extension, empty = "{first stem}-combined"
+    pub page_size: String,   // PDF: "fit" (page wraps the image), "a4", "a5", "letter", "legal"
+    pub orientation: String, // PDF: "auto" (follows each image), "portrait", "landscape"
+    pub fit: String,         // PDF: "contain", "cover" (crop) or "stretch"
+    pub margin_mm: f32,      // PDF: margin on every side
+    pub dpi: u32,            // Print resolution (PDF "fit" page size, TIFF resolution tags)
+    pub jpeg_quality: u8,    // PDF: JPEG quality of the embedded pages
+    pub background: [u8; 3], // RGB, PDF pages flatten transparency over it
+}
+
+impl Default for CombineOptions {
+    fn default() -> Self {
+        CombineOptions {
+            format: "pdf".to_string(),
+            file_name: String::new(),
+            page_size: "a4".to_string(),
+            orientation: "auto".to_string(),
+            fit: "contain".to_string(),
+            margin_mm: 0.0,
+            dpi: 300,
+            jpeg_quality: 85,
+            background: [255, 255, 255],
+        }
+    }
 }
 
 // Event payload for conversion progress
@@ -85,9 +497,54 @@ pub struct ConversionResult {
 pub struct ConversionProgress {
     pub file_id: String,
     pub file_name: String,
-    pub status: String, // "converting" | "completed" | "error" | "skipped"
+    pub status: String, // "converting" | "completed" | "error" | "skipped" | "cancelled"
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_message: Option<String>, // Error message for failed conversions
     #[serde(skip_serializing_if = "Option::is_none")]
     pub saved_path: Option<String>, // Path where converted file was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality_report: Option<QualityReport>, // PSNR / SSIM for completed conversions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<f64>, // 0.0 - 1.0 while converting (video transcodes)
+}
+
+// Video stream information from ffprobe
+#[derive(Serialize, Clone)]
+pub struct VideoInfo {
+    pub format_name: Option<String>,
+    pub duration: Option<f64>, // Seconds
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub bit_rate: Option<u64>,
+}
+
+// ffmpeg transcode options (video containers or animated images)
+#[derive(Deserialize, Clone)]
+pub struct VideoOptions {
+    pub target_format: String, // "mp4" | "mov" | "mkv" | "webm" | "avi" | "gif" | "webp" | "apng"
+    #[serde(default)]
+    pub video_codec: Option<String>, // ffmpeg encoder name, default depends on container
+    #[serde(default)]
+    pub audio_codec: Option<String>, // ffmpeg encoder name, default depends on container
+    #[serde(default)]
+    pub crf: Option<u8>, // Constant rate factor (lower = better quality, larger file)
+    #[serde(default)]
+    pub preset: Option<String>, // Encoder speed preset ("ultrafast" ... "veryslow")
+    #[serde(default)]
+    pub quality: Option<u8>, // 0-100 for animated WebP
+    #[serde(default)]
+    pub width: Option<u32>, // Output width (keeps aspect ratio when height is None)
+    #[serde(default)]
+    pub height: Option<u32>, // Output height (keeps aspect ratio when width is None)
+    #[serde(default)]
+    pub fps: Option<f64>,
+    #[serde(default)]
+    pub start_time: Option<f64>, // Seconds from the start of the input
+    #[serde(default)]
+    pub duration: Option<f64>, // Seconds to convert from start_time
+    #[serde(default)]
+    pub remove_audio: bool,
 }
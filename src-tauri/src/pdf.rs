@@ -0,0 +1,93 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use pdfium_render::prelude::{PdfRenderConfig, Pdfium};
+
+use crate::models::PdfOptions;
+
+// PDF user space unit is 1/72 inch
+const POINTS_PER_INCH: f32 = 72.0;
+
+// Upper bound on the rendered page size (a 4x4 m poster at 300 DPI is still below this)
+const MAX_PAGE_PIXELS: f32 = 20000.0;
+
+pub fn is_pdf(data: &[u8]) -> bool {
+    data.starts_with(b"%PDF-")
+}
+
+// Rasterize the selected pages (1-based, all when empty) at options.dpi
+// Returns (page number, lossless PNG bytes) so pages go through the normal decoders
+pub fn rasterize_pdf(data: &[u8], options: &PdfOptions) -> Result<Vec<(u16, Vec<u8>)>, String> {
+    if !(1..=1200).contains(&options.dpi) {
+        return Err(format!("DPI must be between 1 and 1200 ({})", options.dpi));
+    }
+
+    let pdfium = pdfium()?;
+    let password = (!options.password.is_empty()).then_some(options.password.as_str());
+    let document = pdfium
+        .load_pdf_from_byte_slice(data, password)
+        .map_err(|e| format!("Failed to open PDF: {}", e))?;
+    let pages = document.pages();
+
+    let page_numbers: Vec<u16> = if options.pages.is_empty() {
+        (1..=pages.len()).collect()
+    } else {
+        options.pages.clone()
+    };
+
+    let scale = options.dpi as f32 / POINTS_PER_INCH;
+    let mut rendered = Vec::with_capacity(page_numbers.len());
+    for number in page_numbers {
+        let page = number
+            .checked_sub(1)
+            .and_then(|index| pages.get(index).ok())
+            .ok_or_else(|| format!("PDF has no page {} ({} pages)", number, pages.len()))?;
+
+        let width = page.width().value * scale;
+        let height = page.height().value * scale;
+        if width.max(height) > MAX_PAGE_PIXELS {
+            return Err(format!(
+                "Page {} would be {}x{} pixels at {} DPI",
+                number,
+                width.round(),
+                height.round(),
+                options.dpi
+            ));
+        }
+
+        // Form fields are drawn like in a viewer, transparent areas stay white (default)
+        let config = PdfRenderConfig::new()
+            .scale_page_by_factor(scale)
+            .render_form_data(true);
+        let image = page
+            .render_with_config(&config)
+            .map_err(|e| format!("Failed to render page {}: {}", number, e))?
+            .as_image();
+
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to store page {}: {}", number, e))?;
+        rendered.push((number, png));
+    }
+
+    Ok(rendered)
+}
+
+// PDFium placed next to the executable, the system library otherwise
+fn pdfium() -> Result<Pdfium, String> {
+    let bundled = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .and_then(|dir| {
+            Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(&dir)).ok()
+        });
+
+    let bindings = match bundled {
+        Some(bindings) => bindings,
+        None => Pdfium::bind_to_system_library()
+            .map_err(|e| format!("PDFium library not found: {}", e))?,
+    };
+
+    Ok(Pdfium::new(bindings))
+}
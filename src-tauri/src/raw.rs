@@ -0,0 +1,686 @@
+use std::collections::HashSet;
+use std::io::Cursor;
+
+use image::DynamicImage;
+use rawloader::{RawImage, RawImageData};
+
+use crate::converters::{linear_to_srgb, Rgb16Image};
+use crate::models::RawOptions;
+
+// TIFF tags used to find raw sensor data and embedded previews
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_PHOTOMETRIC: u16 = 0x0106;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+const TAG_DNG_VERSION: u16 = 0xC612;
+
+// Photometric interpretation of sensor data (DNG)
+const PHOTOMETRIC_CFA: usize = 32803;
+const PHOTOMETRIC_LINEAR_RAW: usize = 34892;
+
+// Magic numbers RAW formats use in place of TIFF's 42
+// (ORF "IIRO" / "MMOR" and "IIRS", RW2 "IIU\0"), read in the header's byte order
+const RAW_MAGICS: [u16; 3] = [0x4F52, 0x5352, 0x0055];
+
+// Upper bound on IFDs visited (guards against offset loops in corrupt files)
+const MAX_IFDS: usize = 64;
+
+// Linear sRGB (D65) to XYZ
+const SRGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175],
+    [0.019_333_9, 0.119_192, 0.950_304_1],
+];
+
+// True for TIFF based camera RAW files (DNG, CR2, NEF, ARW, ORF, RW2 ...)
+// Plain TIFF images are left to the image crate
+pub fn is_raw(data: &[u8]) -> bool {
+    let Some(tiff) = Tiff::parse(data) else {
+        return false;
+    };
+
+    // ORF / RW2 use their own magic number instead of 42
+    if RAW_MAGICS.contains(&tiff.magic) {
+        return true;
+    }
+    // BigTIFF (43) and unknown variants stay with the TIFF decoder
+    if tiff.magic != 42 {
+        return false;
+    }
+    // CR2 adds "CR" after the header
+    if data.get(8..10) == Some(b"CR") {
+        return true;
+    }
+
+    tiff.ifds().iter().any(|ifd| {
+        tiff.find(ifd, TAG_DNG_VERSION).is_some()
+            || matches!(
+                tiff.value(ifd, TAG_PHOTOMETRIC),
+                Some(PHOTOMETRIC_CFA | PHOTOMETRIC_LINEAR_RAW)
+            )
+    })
+}
+
+// Decode a camera RAW file: develop the sensor data, or use the embedded preview in fast mode
+pub fn decode_raw(data: &[u8], options: &RawOptions) -> Result<DynamicImage, String> {
+    if options.fast {
+        return embedded_preview(data);
+    }
+
+    let raw = rawloader::decode(&mut Cursor::new(data))
+        .map_err(|e| format!("Failed to decode RAW: {}", e))?;
+    develop(&raw, options)
+}
+
+// Largest embedded JPEG preview that decodes
+fn embedded_preview(data: &[u8]) -> Result<DynamicImage, String> {
+    let tiff = Tiff::parse(data).ok_or_else(|| "Not a TIFF based RAW file".to_string())?;
+
+    let mut candidates: Vec<(usize, usize)> = Vec::new();
+    for ifd in tiff.ifds() {
+        if let (Some(offset), Some(length)) = (
+            tiff.value(&ifd, TAG_JPEG_OFFSET),
+            tiff.value(&ifd, TAG_JPEG_LENGTH),
+        ) {
+            candidates.push((offset, length));
+        }
+
+        // Single strip JPEG that isn't sensor data (CR2 / DNG previews)
+        let compression = tiff.value(&ifd, TAG_COMPRESSION);
+        let photometric = tiff.value(&ifd, TAG_PHOTOMETRIC);
+        if matches!(compression, Some(6 | 7))
+            && !matches!(photometric, Some(PHOTOMETRIC_CFA | PHOTOMETRIC_LINEAR_RAW))
+        {
+            if let (Some(offset), Some(length)) = (
+                tiff.value(&ifd, TAG_STRIP_OFFSETS),
+                tiff.value(&ifd, TAG_STRIP_BYTE_COUNTS),
+            ) {
+                candidates.push((offset, length));
+            }
+        }
+    }
+
+    // Largest first, lossless JPEG raw data also starts with SOI, decoding filters it out
+    candidates.sort_by_key(|&(_, length)| std::cmp::Reverse(length));
+    candidates
+        .into_iter()
+        .filter_map(|(offset, length)| data.get(offset..offset.checked_add(length)?))
+        .filter(|jpeg| jpeg.starts_with(&[0xFF, 0xD8]))
+        .find_map(|jpeg| image::load_from_memory_with_format(jpeg, image::ImageFormat::Jpeg).ok())
+        .ok_or_else(|| "RAW file has no embedded JPEG preview".to_string())
+}
+
+// Black / white level scaling, white balance, bilinear demosaic, camera to sRGB matrix,
+// exposure and sRGB gamma, returned as 16-bit RGB
+fn develop(raw: &RawImage, options: &RawOptions) -> Result<DynamicImage, String> {
+    let (width, height) = (raw.width, raw.height);
+    let [top, right, bottom, left] = raw.crops;
+    let out_width = width.saturating_sub(left + right);
+    let out_height = height.saturating_sub(top + bottom);
+    if out_width == 0 || out_height == 0 {
+        return Err("RAW image has no pixels after cropping".to_string());
+    }
+
+    // Multipliers relative to green, 1.0 when the camera didn't record them
+    let wb = match options.white_balance.as_str() {
+        "camera" if raw.wb_coeffs[..3].iter().all(|c| c.is_finite() && *c > 0.0) => {
+            raw.wb_coeffs.map(|c| c / raw.wb_coeffs[1])
+        }
+        "camera" | "none" => [1.0; 4],
+        other => return Err(format!("Unsupported white balance: {}", other)),
+    };
+
+    // Integer samples scaled to 0.0 - 1.0 between black and white level
+    // (float DNGs are already normalized)
+    let scale = |value: f32, color: usize| -> f32 {
+        match raw.data {
+            RawImageData::Integer(_) => {
+                let black = raw.blacklevels[color] as f32;
+                let white = raw.whitelevels[color] as f32;
+                ((value - black) / (white - black).max(1.0)).max(0.0)
+            }
+            RawImageData::Float(_) => value,
+        }
+    };
+    let sample = |index: usize| -> f32 {
+        match &raw.data {
+            RawImageData::Integer(data) => data[index] as f32,
+            RawImageData::Float(data) => data[index],
+        }
+    };
+
+    let mut rgb = vec![[0f32; 3]; out_width * out_height];
+    match raw.cpp {
+        // Linear raw (demosaiced already)
+        3 => {
+            for (i, pixel) in rgb.iter_mut().enumerate() {
+                let (x, y) = (left + i % out_width, top + i / out_width);
+                let index = (y * width + x) * 3;
+                for (c, value) in pixel.iter_mut().enumerate() {
+                    *value = scale(sample(index + c), c) * wb[c];
+                }
+            }
+        }
+        // Color filter array: average same-color neighbors in a 3x3 window
+        1 => {
+            // Pattern repeats every 2 (Bayer) or 6 (X-Trans) pixels
+            let mut pattern = [[0usize; 6]; 6];
+            for (row, colors) in pattern.iter_mut().enumerate() {
+                for (col, color) in colors.iter_mut().enumerate() {
+                    *color = raw.cfa.color_at(row, col).min(3);
+                }
+            }
+            let color_at = |x: usize, y: usize| pattern[y % 6][x % 6];
+            // 4th filter color (second green / emerald) is averaged into green
+            let channel = |color: usize| if color == 3 { 1 } else { color };
+
+            let mosaic: Vec<f32> = (0..width * height)
+                .map(|i| {
+                    let color = color_at(i % width, i / width);
+                    scale(sample(i), color) * wb[channel(color)]
+                })
+                .collect();
+
+            for (i, pixel) in rgb.iter_mut().enumerate() {
+                let (x, y) = (left + i % out_width, top + i / out_width);
+                let mut sums = [0f32; 3];
+                let mut counts = [0u32; 3];
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                        let c = channel(color_at(nx, ny));
+                        sums[c] += mosaic[ny * width + nx];
+                        counts[c] += 1;
+                    }
+                }
+                for (c, value) in pixel.iter_mut().enumerate() {
+                    *value = sums[c] / counts[c].max(1) as f32;
+                }
+            }
+        }
+        cpp => return Err(format!("Unsupported RAW layout ({} components)", cpp)),
+    }
+
+    let matrix = camera_to_srgb(&raw.xyz_to_cam).unwrap_or([
+        [1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, 0.0, 1.0],
+    ]);
+    let gain = 2f32.powf(options.exposure);
+
+    let pixels: Vec<u16> = rgb
+        .iter()
+        .flat_map(|camera| {
+            matrix.map(|row| {
+                let linear = (row[0] * camera[0] + row[1] * camera[1] + row[2] * camera[2]) * gain;
+                (linear_to_srgb(linear.clamp(0.0, 1.0)) * 65535.0).round() as u16
+            })
+        })
+        .collect();
+
+    Rgb16Image::from_raw(out_width as u32, out_height as u32, pixels)
+        .map(DynamicImage::ImageRgb16)
+        .ok_or_else(|| "Failed to create image from RAW".to_string())
+}
+
+// Camera RGB to linear sRGB: invert sRGB -> camera, rows normalized so white stays white
+// Returns None when the camera has no color matrix
+fn camera_to_srgb(xyz_to_cam: &[[f32; 3]; 4]) -> Option<[[f32; 3]; 3]> {
+    let mut srgb_to_cam = [[0f32; 3]; 3];
+    for (i, row) in srgb_to_cam.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| xyz_to_cam[i][k] * SRGB_TO_XYZ[k][j]).sum();
+        }
+        let sum: f32 = row.iter().sum();
+        if sum.abs() < 1e-6 {
+            return None;
+        }
+        row.iter_mut().for_each(|value| *value /= sum);
+    }
+
+    invert(&srgb_to_cam)
+}
+
+// 3x3 matrix inverse (None when singular)
+fn invert(m: &[[f32; 3]; 3]) -> Option<[[f32; 3]; 3]> {
+    let cofactor =
+        |r1: usize, r2: usize, c1: usize, c2: usize| m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1];
+    let det = m[0][0] * cofactor(1, 2, 1, 2) - m[0][1] * cofactor(1, 2, 0, 2)
+        + m[0][2] * cofactor(1, 2, 0, 1);
+    if det.abs() < 1e-9 {
+        return None;
+    }
+
+    Some([
+        [
+            cofactor(1, 2, 1, 2) / det,
+            -cofactor(0, 2, 1, 2) / det,
+            cofactor(0, 1, 1, 2) / det,
+        ],
+        [
+            -cofactor(1, 2, 0, 2) / det,
+            cofactor(0, 2, 0, 2) / det,
+            -cofactor(0, 1, 0, 2) / det,
+        ],
+        [
+            cofactor(1, 2, 0, 1) / det,
+            -cofactor(0, 2, 0, 1) / det,
+            cofactor(0, 1, 0, 1) / det,
+        ],
+    ])
+}
+
+// Minimal TIFF directory reader (rawloader doesn't expose previews)
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+    magic: u16,
+    first_ifd: usize,
+}
+
+// Directory entry: tag, field type, value count and position of the value (or its offset)
+struct Entry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    position: usize,
+}
+
+impl<'a> Tiff<'a> {
+    fn parse(data: &'a [u8]) -> Option<Tiff<'a>> {
+        let little_endian = match data.get(0..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        let mut tiff = Tiff {
+            data,
+            little_endian,
+            magic: 0,
+            first_ifd: 0,
+        };
+        tiff.magic = tiff.u16_at(2)?;
+        tiff.first_ifd = tiff.u32_at(4)? as usize;
+        Some(tiff)
+    }
+
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    // Every IFD reachable from IFD0 through next-IFD links and SubIFDs
+    fn ifds(&self) -> Vec<Vec<Entry>> {
+        let mut ifds = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![self.first_ifd];
+
+        while let Some(offset) = pending.pop() {
+            if offset == 0 || ifds.len() >= MAX_IFDS || !visited.insert(offset) {
+                continue;
+            }
+            let Some(entries) = self.entries(offset) else {
+                continue;
+            };
+
+            if let Some(next) = self.u32_at(offset + 2 + entries.len() * 12) {
+                pending.push(next as usize);
+            }
+            if let Some(sub_ifds) = self.find(&entries, TAG_SUB_IFDS) {
+                pending.extend((0..sub_ifds.count).filter_map(|i| self.element(sub_ifds, i)));
+            }
+            ifds.push(entries);
+        }
+
+        ifds
+    }
+
+    fn entries(&self, offset: usize) -> Option<Vec<Entry>> {
+        let count = self.u16_at(offset)? as usize;
+        (0..count)
+            .map(|i| {
+                let entry = offset + 2 + i * 12;
+                Some(Entry {
+                    tag: self.u16_at(entry)?,
+                    field_type: self.u16_at(entry + 2)?,
+                    count: self.u32_at(entry + 4)?,
+                    position: entry + 8,
+                })
+            })
+            .collect()
+    }
+
+    fn find<'e>(&self, entries: &'e [Entry], tag: u16) -> Option<&'e Entry> {
+        entries.iter().find(|entry| entry.tag == tag)
+    }
+
+    // Value of a single valued SHORT / LONG tag
+    fn value(&self, entries: &[Entry], tag: u16) -> Option<usize> {
+        let entry = self.find(entries, tag)?;
+        if entry.count != 1 {
+            return None;
+        }
+        self.element(entry, 0)
+    }
+
+    // Element i of a SHORT / LONG / IFD array, inline when it fits in 4 bytes
+    fn element(&self, entry: &Entry, i: u32) -> Option<usize> {
+        let size = match entry.field_type {
+            3 => 2,
+            4 | 13 => 4,
+            _ => return None,
+        };
+        let base = if size * entry.count as usize <= 4 {
+            entry.position
+        } else {
+            self.u32_at(entry.position)? as usize
+        };
+        let offset = base + size * i as usize;
+        match size {
+            2 => self.u16_at(offset).map(usize::from),
+            _ => self.u32_at(offset).map(|v| v as usize),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Little endian TIFF with one IFD of single SHORT entries
+    fn little_endian_tiff(magic: &[u8; 2], entries: &[(u16, u16)]) -> Vec<u8> {
+        let mut data = b"II".to_vec();
+        data.extend_from_slice(magic);
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for &(tag, value) in entries {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&3u16.to_le_bytes());
+            data.extend_from_slice(&1u32.to_le_bytes());
+            data.extend_from_slice(&u32::from(value).to_le_bytes());
+        }
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn plain_tiff_and_bigtiff_are_not_raw() {
+        assert!(!is_raw(&little_endian_tiff(
+            b"*\0",
+            &[(TAG_PHOTOMETRIC, 2)]
+        )));
+        assert!(!is_raw(b"II+\0\x08\0\0\0\x10\0\0\0\0\0\0\0"));
+        assert!(!is_raw(b"MM\0+\0\x08\0\0\0\0\0\0\0\0\0\x10"));
+        // Unknown magic, even with sensor data
+        assert!(!is_raw(&little_endian_tiff(
+            b"\x2C\0",
+            &[(TAG_PHOTOMETRIC, PHOTOMETRIC_CFA as u16)]
+        )));
+    }
+
+    #[test]
+    fn raw_magics_are_raw() {
+        for header in [b"IIRO", b"IIRS", b"MMOR", b"IIU\0"] {
+            let mut data = header.to_vec();
+            data.extend_from_slice(&[0; 12]);
+            assert!(is_raw(&data), "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn detects_cr2_and_dng_headers() {
+        let mut cr2 = b"II*\0\x10\0\0\0CR\x02\0\0\0\0\0".to_vec();
+        cr2.extend_from_slice(&[0; 6]);
+        assert!(is_raw(&cr2));
+
+        assert!(is_raw(&little_endian_tiff(b"*\0", &[(TAG_DNG_VERSION, 1)])));
+        assert!(is_raw(&little_endian_tiff(
+            b"*\0",
+            &[(TAG_PHOTOMETRIC, PHOTOMETRIC_LINEAR_RAW as u16)]
+        )));
+    }
+
+    #[test]
+    fn truncated_headers_are_not_raw() {
+        assert!(!is_raw(b""));
+        assert!(!is_raw(b"II"));
+        assert!(!is_raw(b"II*\0\x08"));
+        // IFD offset past the end of the file
+        assert!(!is_raw(b"II*\0\xFF\0\0\0"));
+    }
+
+    // IFD with (tag, type, count, value) entries and a next-IFD offset
+    fn ifd(entries: &[(u16, u16, u32, u32)], next: u32) -> Vec<u8> {
+        let mut data = (entries.len() as u16).to_le_bytes().to_vec();
+        for &(tag, field_type, count, value) in entries {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&field_type.to_le_bytes());
+            data.extend_from_slice(&count.to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&next.to_le_bytes());
+        data
+    }
+
+    fn header() -> Vec<u8> {
+        b"II*\0\x08\0\0\0".to_vec()
+    }
+
+    #[test]
+    fn follows_next_links_and_sub_ifds() {
+        // IFD0 (8..26) with two SubIFDs listed at 26, sub IFDs at 34 and 52
+        let mut data = header();
+        data.extend(ifd(&[(TAG_SUB_IFDS, 4, 2, 26)], 0));
+        data.extend_from_slice(&34u32.to_le_bytes());
+        data.extend_from_slice(&52u32.to_le_bytes());
+        data.extend(ifd(&[(TAG_COMPRESSION, 3, 1, 6)], 0));
+        data.extend(ifd(&[(TAG_COMPRESSION, 3, 1, 7)], 70));
+        data.extend(ifd(&[(TAG_COMPRESSION, 3, 1, 1)], 0));
+
+        let tiff = Tiff::parse(&data).unwrap();
+        let mut compressions: Vec<usize> = tiff
+            .ifds()
+            .iter()
+            .filter_map(|ifd| tiff.value(ifd, TAG_COMPRESSION))
+            .collect();
+        compressions.sort_unstable();
+        assert_eq!(compressions, [1, 6, 7]);
+    }
+
+    #[test]
+    fn ifd_cycles_are_read_once() {
+        let mut data = header();
+        data.extend(ifd(&[(TAG_COMPRESSION, 3, 1, 1)], 8));
+        assert_eq!(Tiff::parse(&data).unwrap().ifds().len(), 1);
+    }
+
+    #[test]
+    fn truncated_ifds_are_skipped() {
+        // Three entries announced, one present
+        let mut data = header();
+        data.extend(ifd(&[(TAG_COMPRESSION, 3, 1, 1)], 0));
+        data[8] = 3;
+        assert!(Tiff::parse(&data).unwrap().ifds().is_empty());
+
+        // Array value pointing past the end of the file
+        let mut data = header();
+        data.extend(ifd(&[(TAG_SUB_IFDS, 4, 2, 0xFFFF)], 0));
+        let tiff = Tiff::parse(&data).unwrap();
+        assert_eq!(tiff.ifds().len(), 1);
+
+        assert!(Tiff::parse(b"II*\0\x08").is_none());
+    }
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(width, height, image::Rgb([200, 100, 50]));
+        let mut data = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new(&mut data)
+            .encode_image(&img)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn preview_is_largest_decodable_jpeg() {
+        let small = jpeg(8, 4);
+        let large = jpeg(32, 16);
+        // A bigger candidate that only looks like a JPEG is passed over
+        let broken = [&[0xFF, 0xD8][..], &[0; 4096]].concat();
+
+        let mut data = header();
+        let start = 8 + 2 + 3 * 12 + 4 + 2 + 2 * 12 + 4;
+        let large_at = (start + small.len()) as u32;
+        let broken_at = large_at + large.len() as u32;
+        data.extend(ifd(
+            &[
+                (TAG_COMPRESSION, 3, 1, 6),
+                (TAG_STRIP_OFFSETS, 4, 1, start as u32),
+                (TAG_STRIP_BYTE_COUNTS, 4, 1, small.len() as u32),
+            ],
+            (8 + 2 + 3 * 12 + 4) as u32,
+        ));
+        data.extend(ifd(
+            &[
+                (TAG_JPEG_OFFSET, 4, 1, large_at),
+                (TAG_JPEG_LENGTH, 4, 1, large.len() as u32),
+            ],
+            0,
+        ));
+        data.extend(&small);
+        data.extend(&large);
+        data.extend(&broken);
+
+        let preview = embedded_preview(&data).unwrap();
+        assert_eq!((preview.width(), preview.height()), (32, 16));
+
+        // Same candidates, but the large one is cut off by the end of the file
+        data.truncate(broken_at as usize - 10);
+        let preview = embedded_preview(&data).unwrap();
+        assert_eq!((preview.width(), preview.height()), (8, 4));
+    }
+
+    #[test]
+    fn missing_preview_is_an_error() {
+        let mut data = header();
+        data.extend(ifd(
+            &[(TAG_JPEG_OFFSET, 4, 1, 1000), (TAG_JPEG_LENGTH, 4, 1, 10)],
+            0,
+        ));
+        assert!(embedded_preview(&data).is_err());
+        assert!(embedded_preview(b"not a tiff").is_err());
+    }
+
+    // 12-bit RGGB sensor without a color matrix (identity) or white balance
+    fn sensor(width: usize, height: usize, value: impl Fn(usize) -> u16) -> RawImage {
+        let cfa = rawloader::CFA::new("RGGB");
+        let data = (0..width * height)
+            .map(|i| value(cfa.color_at(i / width, i % width)))
+            .collect();
+        RawImage {
+            make: String::new(),
+            model: String::new(),
+            clean_make: String::new(),
+            clean_model: String::new(),
+            width,
+            height,
+            cpp: 1,
+            wb_coeffs: [f32::NAN; 4],
+            whitelevels: [4095; 4],
+            blacklevels: [0; 4],
+            xyz_to_cam: [[0.0; 3]; 4],
+            cfa,
+            crops: [0; 4],
+            blackareas: Vec::new(),
+            orientation: rawloader::Orientation::Normal,
+            data: RawImageData::Integer(data),
+        }
+    }
+
+    fn pixels(img: &DynamicImage) -> Vec<[u16; 3]> {
+        img.to_rgb16().pixels().map(|px| px.0).collect()
+    }
+
+    #[test]
+    fn demosaic_fills_every_channel() {
+        let options = RawOptions::default();
+        // Red sites full, green and blue empty: solid red everywhere
+        let red = develop(
+            &sensor(6, 4, |color| if color == 0 { 4095 } else { 0 }),
+            &options,
+        );
+        assert!(pixels(&red.unwrap()).iter().all(|&px| px == [65535, 0, 0]));
+
+        // Flat mid gray stays neutral
+        let gray = develop(&sensor(5, 3, |_| 2048), &options).unwrap();
+        let expected = (linear_to_srgb(2048.0 / 4095.0) * 65535.0).round() as u16;
+        assert!(pixels(&gray).iter().all(|&px| px == [expected; 3]));
+    }
+
+    #[test]
+    fn crops_and_black_level_are_applied() {
+        let mut raw = sensor(6, 6, |_| 256);
+        raw.crops = [1, 2, 1, 1];
+        raw.blacklevels = [256; 4];
+        let img = develop(&raw, &RawOptions::default()).unwrap();
+        assert_eq!((img.width(), img.height()), (3, 4));
+        assert!(pixels(&img).iter().all(|&px| px == [0; 3]));
+
+        raw.crops = [3, 3, 3, 3];
+        assert!(develop(&raw, &RawOptions::default()).is_err());
+    }
+
+    #[test]
+    fn single_photosite_develops() {
+        let img = develop(&sensor(1, 1, |_| 4095), &RawOptions::default()).unwrap();
+        // Only a red site: green and blue have no neighbors to borrow from
+        assert_eq!(pixels(&img), [[65535, 0, 0]]);
+    }
+
+    #[test]
+    fn camera_white_balance_scales_red() {
+        let mut raw = sensor(4, 4, |_| 1024);
+        raw.wb_coeffs = [2.0, 1.0, 1.0, f32::NAN];
+        let camera = pixels(&develop(&raw, &RawOptions::default()).unwrap());
+        let none = RawOptions {
+            white_balance: "none".to_string(),
+            ..RawOptions::default()
+        };
+        let neutral = pixels(&develop(&raw, &none).unwrap());
+        assert!(camera[0][0] > neutral[0][0]);
+        assert_eq!(camera[0][1], neutral[0][1]);
+
+        let unknown = RawOptions {
+            white_balance: "auto".to_string(),
+            ..RawOptions::default()
+        };
+        assert!(develop(&raw, &unknown).is_err());
+    }
+
+    #[test]
+    fn inverts_matrices() {
+        let m = [[2.0, 0.0, 1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 1.0]];
+        let inverse = invert(&m).unwrap();
+        for (i, row) in m.iter().enumerate() {
+            for (j, column) in (0..3).map(|j| inverse.map(|r| r[j])).enumerate() {
+                let value: f32 = row.iter().zip(column).map(|(a, b)| a * b).sum();
+                assert!((value - if i == j { 1.0 } else { 0.0 }).abs() < 1e-6);
+            }
+        }
+        assert!(invert(&[[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 0.0, 1.0]]).is_none());
+    }
+}
@@ -0,0 +1,329 @@
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+
+use crate::converters::encode_image;
+use crate::models::{ConversionOptions, SrcsetImage, SrcsetOptions, SrcsetResult};
+
+// Error prefix when part of the set is already on disk (file is skipped)
+pub const OUTPUT_EXISTS: &str = "File already exists";
+
+// Every width x format of a responsive image set, written to dir as {stem}-{width}w.{ext}
+// The last format is the <img> fallback, the others become <source> elements
+// A JSON manifest of the set is written next to it as {stem}-srcset.json
+pub fn write_srcset(
+    img: &image::DynamicImage,
+    stem: &str,
+    dir: &Path,
+    options: &ConversionOptions,
+    srcset: &SrcsetOptions,
+    exif_bytes: Option<&[u8]>,
+) -> Result<SrcsetResult, String> {
+    if srcset.formats.is_empty() {
+        return Err("No srcset formats given".to_string());
+    }
+    let widths = srcset_widths(img.width(), &srcset.widths);
+
+    // Skip the whole set when any output exists, a partial set would break the snippet
+    let names = srcset
+        .formats
+        .iter()
+        .flat_map(|format| widths.iter().map(|&width| output_name(stem, width, format)))
+        .chain(std::iter::once(manifest_name(stem)));
+    for name in names {
+        if dir.join(&name).exists() {
+            return Err(format!("{}: {}", OUTPUT_EXISTS, name));
+        }
+    }
+
+    // Files are written under temporary names and only renamed once the whole set
+    // succeeded, so a failed encode or write leaves nothing behind
+    let mut written = Vec::new();
+    let result = write_set(
+        img,
+        stem,
+        dir,
+        options,
+        srcset,
+        exif_bytes,
+        &widths,
+        &mut written,
+    )
+    .and_then(|set| rename_all(&written).map(|_| set));
+    if result.is_err() {
+        for (temp, _) in &written {
+            let _ = std::fs::remove_file(temp);
+        }
+    }
+    result
+}
+
+// Write a set of files to dir all or nothing, returns their final paths
+pub fn write_all<N: AsRef<str>>(
+    dir: &Path,
+    files: &[(N, Vec<u8>)],
+) -> Result<Vec<PathBuf>, String> {
+    let mut written = Vec::new();
+    let result = files
+        .iter()
+        .map(|(name, data)| write_temp(dir, name.as_ref(), data, &mut written))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|paths| rename_all(&written).map(|_| paths));
+    if result.is_err() {
+        for (temp, _) in &written {
+            let _ = std::fs::remove_file(temp);
+        }
+    }
+    result
+}
+
+// Encode every image and the manifest to temporary files, recording (temp, final) paths
+#[allow(clippy::too_many_arguments)]
+fn write_set(
+    img: &image::DynamicImage,
+    stem: &str,
+    dir: &Path,
+    options: &ConversionOptions,
+    srcset: &SrcsetOptions,
+    exif_bytes: Option<&[u8]>,
+    widths: &[u32],
+    written: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<SrcsetResult, String> {
+    let mut images = Vec::with_capacity(widths.len() * srcset.formats.len());
+    for &width in widths {
+        // Each width is resized once and encoded to every format
+        let height = ((img.height() as u64 * width as u64) / img.width().max(1) as u64).max(1);
+        let resized = if width == img.width() {
+            img.clone()
+        } else {
+            img.resize_exact(width, height as u32, FilterType::Lanczos3)
+        };
+
+        for format in &srcset.formats {
+            let options = ConversionOptions {
+                target_format: format.clone(),
+                ..options.clone()
+            };
+            let encoded = encode_image(&resized, &options, exif_bytes)?;
+
+            let name = output_name(stem, width, format);
+            let path = write_temp(dir, &name, &encoded.data, written)?;
+
+            images.push(SrcsetImage {
+                file_name: name,
+                saved_path: path.to_string_lossy().to_string(),
+                format: format.clone(),
+                width: resized.width(),
+                height: resized.height(),
+                size: encoded.data.len() as u64,
+            });
+        }
+    }
+
+    let set = SrcsetResult {
+        html: picture_html(&images, &srcset.formats, srcset),
+        sizes: srcset.sizes.clone(),
+        manifest_path: dir.join(manifest_name(stem)).to_string_lossy().to_string(),
+        images,
+    };
+    let manifest = serde_json::to_vec_pretty(&set)
+        .map_err(|e| format!("Failed to create srcset manifest: {}", e))?;
+    write_temp(dir, &manifest_name(stem), &manifest, written)?;
+
+    Ok(set)
+}
+
+// Write data as a hidden .part file next to its final name, returns the final path
+fn write_temp(
+    dir: &Path,
+    name: &str,
+    data: &[u8],
+    written: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<PathBuf, String> {
+    let temp = dir.join(format!(".{}.part", name));
+    let path = dir.join(name);
+    written.push((temp.clone(), path.clone()));
+    std::fs::write(&temp, data).map_err(|e| format!("Failed to write file: {}", e))?;
+    Ok(path)
+}
+
+// Move every file to its final name, undoing the renames already done on failure
+fn rename_all(written: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    for (index, (temp, path)) in written.iter().enumerate() {
+        if let Err(e) = std::fs::rename(temp, path) {
+            for (_, renamed) in &written[..index] {
+                let _ = std::fs::remove_file(renamed);
+            }
+            return Err(format!("Failed to write file: {}", e));
+        }
+    }
+    Ok(())
+}
+
+fn output_name(stem: &str, width: u32, format: &str) -> String {
+    format!("{}-{}w.{}", stem, width, format)
+}
+
+fn manifest_name(stem: &str) -> String {
+    format!("{}-srcset.json", stem)
+}
+
+// Requested widths capped to the source width (never upscaled)
+fn srcset_widths(source_width: u32, requested: &[u32]) -> Vec<u32> {
+    let mut widths: Vec<u32> = requested
+        .iter()
+        .filter(|&&width| width > 0)
+        .map(|&width| width.min(source_width))
+        .collect();
+    if widths.is_empty() {
+        widths.push(source_width);
+    }
+
+    widths.sort_unstable();
+    widths.dedup();
+    widths
+}
+
+// <picture> with one <source> per format, <img> for the last format at the largest width
+fn picture_html(images: &[SrcsetImage], formats: &[String], srcset: &SrcsetOptions) -> String {
+    let candidates = |format: &str| {
+        images
+            .iter()
+            .filter(|image| image.format == format)
+            .map(|image| {
+                format!(
+                    "{} {}w",
+                    url(&srcset.url_prefix, &image.file_name),
+                    image.width
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let sizes = escape(&srcset.sizes);
+
+    let Some((fallback_format, source_formats)) = formats.split_last() else {
+        return String::new();
+    };
+
+    let mut html = String::from("<picture>\n");
+    for format in source_formats {
+        html.push_str(&format!(
+            "  <source type=\"{}\" srcset=\"{}\" sizes=\"{}\">\n",
+            mime_type(format),
+            candidates(format),
+            sizes
+        ));
+    }
+
+    if let Some(fallback) = images
+        .iter()
+        .filter(|image| &image.format == fallback_format)
+        .max_by_key(|image| image.width)
+    {
+        html.push_str(&format!(
+            "  <img src=\"{}\" srcset=\"{}\" sizes=\"{}\" width=\"{}\" height=\"{}\" alt=\"\" loading=\"lazy\" decoding=\"async\">\n",
+            url(&srcset.url_prefix, &fallback.file_name),
+            candidates(fallback_format),
+            sizes,
+            fallback.width,
+            fallback.height
+        ));
+    }
+    html.push_str("</picture>");
+    html
+}
+
+// Spaces separate srcset candidates, encode them in file names
+fn url(prefix: &str, file_name: &str) -> String {
+    escape(&format!("{}{}", prefix, file_name).replace(' ', "%20"))
+}
+
+// Escape for use inside a double-quoted HTML attribute
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn mime_type(format: &str) -> String {
+    match format {
+        "jpg" | "jpeg" => "image/jpeg".to_string(),
+        format => format!("image/{}", format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(format: &str, width: u32, file_name: &str) -> SrcsetImage {
+        SrcsetImage {
+            file_name: file_name.to_string(),
+            saved_path: String::new(),
+            format: format.to_string(),
+            width,
+            height: width / 2,
+            size: 0,
+        }
+    }
+
+    fn srcset(formats: &[&str], sizes: &str, url_prefix: &str) -> SrcsetOptions {
+        SrcsetOptions {
+            formats: formats.iter().map(|f| f.to_string()).collect(),
+            sizes: sizes.to_string(),
+            url_prefix: url_prefix.to_string(),
+            ..SrcsetOptions::default()
+        }
+    }
+
+    #[test]
+    fn widths_are_capped_and_deduplicated() {
+        assert_eq!(
+            srcset_widths(1000, &[1920, 320, 640, 1280, 320]),
+            [320, 640, 1000]
+        );
+        // Zero widths are ignored, nothing left means the source width
+        assert_eq!(srcset_widths(800, &[0]), [800]);
+        assert_eq!(srcset_widths(800, &[]), [800]);
+    }
+
+    #[test]
+    fn picture_uses_last_format_at_largest_width_as_fallback() {
+        let images = [
+            image("avif", 320, "a-320w.avif"),
+            image("avif", 640, "a-640w.avif"),
+            image("jpeg", 320, "a-320w.jpeg"),
+            image("jpeg", 640, "a-640w.jpeg"),
+        ];
+        let options = srcset(&["avif", "jpeg"], "100vw", "/img/");
+        let html = picture_html(&images, &options.formats, &options);
+
+        assert_eq!(
+            html,
+            "<picture>\n  \
+             <source type=\"image/avif\" srcset=\"/img/a-320w.avif 320w, /img/a-640w.avif 640w\" sizes=\"100vw\">\n  \
+             <img src=\"/img/a-640w.jpeg\" srcset=\"/img/a-320w.jpeg 320w, /img/a-640w.jpeg 640w\" sizes=\"100vw\" \
+             width=\"640\" height=\"320\" alt=\"\" loading=\"lazy\" decoding=\"async\">\n\
+             </picture>"
+        );
+        assert_eq!(picture_html(&images, &[], &options), "");
+    }
+
+    #[test]
+    fn attributes_are_escaped() {
+        let images = [image("webp", 320, "my photo.webp")];
+        let options = srcset(&["webp"], "(min-width: 800px) \"50vw\"", "/a&b/");
+        let html = picture_html(&images, &options.formats, &options);
+
+        assert!(html.contains("src=\"/a&amp;b/my%20photo.webp\""));
+        assert!(html.contains("sizes=\"(min-width: 800px) &quot;50vw&quot;\""));
+        assert_eq!(
+            escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+        assert_eq!(url("/images/", "a b\".png"), "/images/a%20b&quot;.png");
+    }
+}
@@ -1,6 +1,15 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use crate::models::FileItem;
+use crate::video::VideoJob;
 
 // Global state for file list (Arc allows cloning for async tasks)
 pub struct FileListState(pub Arc<Mutex<Vec<FileItem>>>);
+
+// Video conversion state: configured ffmpeg location and running video jobs
+pub struct VideoState {
+    pub ffmpeg_path: Arc<Mutex<Option<PathBuf>>>, // None = next to the executable or PATH
+    pub jobs: Arc<Mutex<HashMap<String, VideoJob>>>, // Running jobs by file id, one per file
+}
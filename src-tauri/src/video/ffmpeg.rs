@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Locate ffmpeg: configured path, next to the app executable, then PATH
+pub fn locate_ffmpeg(configured: Option<&Path>) -> Result<PathBuf, String> {
+    locate("ffmpeg", configured)
+}
+
+// Locate ffprobe (expected next to ffmpeg when a path is configured)
+pub fn locate_ffprobe(configured: Option<&Path>) -> Result<PathBuf, String> {
+    locate("ffprobe", configured)
+}
+
+// Command without a console window on Windows
+pub fn command(program: &Path) -> Command {
+    #[allow(unused_mut)]
+    let mut command = Command::new(program);
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    command
+}
+
+fn locate(name: &str, configured: Option<&Path>) -> Result<PathBuf, String> {
+    let file_name = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+
+    // Configured path may point at the ffmpeg binary or the directory containing it
+    if let Some(path) = configured {
+        let candidate = if path.is_dir() {
+            path.join(&file_name)
+        } else {
+            path.with_file_name(&file_name)
+        };
+        if candidate.is_file() {
+            return Ok(candidate);
+        }
+        return Err(format!("{} not found at {}", name, candidate.display()));
+    }
+
+    // Copied next to the app executable (ffmpeg is not bundled with the app)
+    if let Some(dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        let candidate = dir.join(&file_name);
+        if candidate.is_file() {
+            return Ok(candidate);
+        }
+    }
+
+    // PATH, plus Homebrew / MacPorts directories missing from the PATH of GUI apps on macOS
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).collect())
+        .unwrap_or_default();
+    if cfg!(target_os = "macos") {
+        dirs.extend(["/opt/homebrew/bin", "/usr/local/bin", "/opt/local/bin"].map(PathBuf::from));
+    }
+
+    dirs.into_iter()
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| format!("{} not found (install ffmpeg or set its path)", name))
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+
+use super::ffmpeg::command;
+use super::jobs::{attach_process, detach_process, VideoJob};
+
+// Upper bound on frames per extraction (small intervals on long clips)
+const MAX_FRAMES: usize = 1000;
+
+// Timestamps for interval mode: 0, interval, 2 * interval ... before the end of the clip
+pub fn interval_timestamps(interval: f64, duration: f64) -> Result<Vec<f64>, String> {
+    if interval.is_nan() || interval <= 0.0 {
+        return Err("Frame interval must be greater than 0".to_string());
+    }
+
+    let count = (duration / interval).ceil().max(1.0) as usize;
+    if count > MAX_FRAMES {
+        return Err(format!(
+            "Interval of {}s would extract {} frames (limit {})",
+            interval, count, MAX_FRAMES
+        ));
+    }
+
+    Ok((0..count).map(|i| i as f64 * interval).collect())
+}
+
+// Check explicit timestamps against the frame limit
+pub fn check_timestamps(timestamps: &[f64]) -> Result<(), String> {
+    if timestamps.is_empty() {
+        return Err("No timestamps given".to_string());
+    }
+    if timestamps.len() > MAX_FRAMES {
+        return Err(format!("Too many timestamps (limit {})", MAX_FRAMES));
+    }
+    if let Some(t) = timestamps.iter().find(|t| !t.is_finite() || **t < 0.0) {
+        return Err(format!("Invalid timestamp: {}", t));
+    }
+
+    Ok(())
+}
+
+// Decode the frame at timestamp (seconds) as PNG bytes
+// The process is registered in jobs under job_id, returns None when cancel_job killed it
+pub fn extract_frame(
+    ffmpeg: &Path,
+    input: &Path,
+    timestamp: f64,
+    jobs: &Arc<Mutex<HashMap<String, VideoJob>>>,
+    job_id: &str,
+) -> Result<Option<Vec<u8>>, String> {
+    // PNG keeps full precision (16-bit for high bit depth video) for the converters
+    let mut child = command(ffmpeg)
+        .args(["-hide_banner", "-nostdin", "-v", "error"])
+        .arg("-ss")
+        .arg(timestamp.to_string())
+        .arg("-i")
+        .arg(input)
+        .args([
+            "-frames:v",
+            "1",
+            "-f",
+            "image2pipe",
+            "-c:v",
+            "png",
+            "pipe:1",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    let stdout = child.stdout.take().ok_or("Failed to read ffmpeg output")?;
+    let stderr = child.stderr.take().ok_or("Failed to read ffmpeg output")?;
+
+    // Drain stderr on its own thread so a full pipe cannot block ffmpeg
+    let stderr_reader = std::thread::spawn(move || {
+        let mut message = String::new();
+        let _ = BufReader::new(stderr).read_to_string(&mut message);
+        message
+    });
+
+    if !attach_process(jobs, job_id, child) {
+        let _ = stderr_reader.join();
+        return Ok(None);
+    }
+
+    let mut data = Vec::new();
+    let read = BufReader::new(stdout).read_to_end(&mut data);
+
+    let child = detach_process(jobs, job_id);
+    let message = stderr_reader.join().unwrap_or_default();
+
+    // Process already taken: cancel_job killed it
+    let Some(mut child) = child else {
+        return Ok(None);
+    };
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for ffmpeg: {}", e))?;
+    read.map_err(|e| format!("Failed to read ffmpeg output: {}", e))?;
+    if !status.success() {
+        let reason = message.lines().last().unwrap_or("unknown error").trim();
+        return Err(format!("ffmpeg failed ({}): {}", status, reason));
+    }
+
+    // Seeking past the end succeeds without writing a frame
+    if data.is_empty() {
+        return Err(format!("No frame at {}s", timestamp));
+    }
+
+    Ok(Some(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_covers_the_clip() {
+        assert_eq!(interval_timestamps(1.0, 3.5).unwrap(), [0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(interval_timestamps(2.0, 4.0).unwrap(), [0.0, 2.0]);
+        // Clips shorter than the interval (or of unknown length) still give the first frame
+        assert_eq!(interval_timestamps(5.0, 1.0).unwrap(), [0.0]);
+        assert_eq!(interval_timestamps(1.0, f64::NAN).unwrap(), [0.0]);
+    }
+
+    #[test]
+    fn interval_respects_the_frame_limit() {
+        assert_eq!(interval_timestamps(1.0, 1000.0).unwrap().len(), MAX_FRAMES);
+        assert!(interval_timestamps(1.0, 1000.5).is_err());
+        assert!(interval_timestamps(1.0, f64::INFINITY).is_err());
+        for interval in [0.0, -1.0, f64::NAN] {
+            assert!(interval_timestamps(interval, 10.0).is_err());
+        }
+    }
+
+    #[test]
+    fn timestamps_are_checked() {
+        assert!(check_timestamps(&[0.0, 1.5, 0.5]).is_ok());
+        assert!(check_timestamps(&[0.0; MAX_FRAMES]).is_ok());
+        assert!(check_timestamps(&[0.0; MAX_FRAMES + 1]).is_err());
+        assert!(check_timestamps(&[]).is_err());
+        for invalid in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(check_timestamps(&[0.0, invalid]).is_err());
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use std::path::{Path, PathBuf};
+
+// ffmpeg input file: the original file, or a temporary copy for files added from URLs
+// Temporary copies are deleted when dropped
+pub struct VideoInput {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl VideoInput {
+    pub fn new(source_path: Option<&str>, data: &[u8], name: &str) -> Result<Self, String> {
+        if let Some(path) = source_path {
+            return Ok(VideoInput {
+                path: PathBuf::from(path),
+                temporary: false,
+            });
+        }
+
+        // Keep the extension so ffmpeg can use it as a format hint
+        let extension = Path::new(name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("tmp");
+        let path = std::env::temp_dir().join(format!("{}.{}", uuid::Uuid::new_v4(), extension));
+        std::fs::write(&path, data)
+            .map_err(|e| format!("Failed to write temporary video file: {}", e))?;
+
+        Ok(VideoInput {
+            path,
+            temporary: true,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for VideoInput {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
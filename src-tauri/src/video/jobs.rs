@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+
+// A running video command: its current ffmpeg process (None between frames)
+// and whether it was cancelled
+pub struct VideoJob {
+    child: Option<Child>,
+    cancelled: bool,
+}
+
+// Reservation of a file for one video job, released when dropped
+pub struct JobGuard {
+    jobs: Arc<Mutex<HashMap<String, VideoJob>>>,
+    job_id: String,
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        self.jobs.lock().unwrap().remove(&self.job_id);
+    }
+}
+
+// Reserve job_id for a new job, fails while another job for it is still running
+// (a cancelled job keeps its reservation until it has stopped)
+pub fn start_job(
+    jobs: &Arc<Mutex<HashMap<String, VideoJob>>>,
+    job_id: &str,
+) -> Result<JobGuard, String> {
+    let mut running = jobs.lock().unwrap();
+    if running.contains_key(job_id) {
+        return Err("A video conversion is already running for this file".to_string());
+    }
+    running.insert(
+        job_id.to_string(),
+        VideoJob {
+            child: None,
+            cancelled: false,
+        },
+    );
+
+    Ok(JobGuard {
+        jobs: jobs.clone(),
+        job_id: job_id.to_string(),
+    })
+}
+
+// Register the running process so cancel_job can kill it
+// Returns false (and kills the process) when the job was cancelled before it started
+pub fn attach_process(
+    jobs: &Arc<Mutex<HashMap<String, VideoJob>>>,
+    job_id: &str,
+    mut child: Child,
+) -> bool {
+    let mut running = jobs.lock().unwrap();
+    match running.get_mut(job_id) {
+        Some(job) if !job.cancelled => {
+            job.child = Some(child);
+            true
+        }
+        _ => {
+            drop(running);
+            let _ = child.kill();
+            let _ = child.wait();
+            false
+        }
+    }
+}
+
+// Take the process back once its output is closed, None when cancel_job killed it
+pub fn detach_process(jobs: &Arc<Mutex<HashMap<String, VideoJob>>>, job_id: &str) -> Option<Child> {
+    jobs.lock()
+        .unwrap()
+        .get_mut(job_id)
+        .and_then(|job| job.child.take())
+}
+
+// Cancel a job and kill its running ffmpeg process, returns false when no job is running
+pub fn cancel_job(jobs: &Arc<Mutex<HashMap<String, VideoJob>>>, job_id: &str) -> bool {
+    let child = {
+        let mut running = jobs.lock().unwrap();
+        let Some(job) = running.get_mut(job_id) else {
+            return false;
+        };
+        job.cancelled = true;
+        job.child.take()
+    };
+
+    if let Some(mut child) = child {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    true
+}
@@ -0,0 +1,13 @@
+mod ffmpeg;
+mod frames;
+mod input;
+mod jobs;
+mod probe;
+mod transcode;
+
+pub use ffmpeg::{locate_ffmpeg, locate_ffprobe};
+pub use frames::{check_timestamps, extract_frame, interval_timestamps};
+pub use input::VideoInput;
+pub use jobs::{cancel_job, start_job, VideoJob};
+pub use probe::probe_video;
+pub use transcode::{output_extension, transcode, TranscodeOutcome};
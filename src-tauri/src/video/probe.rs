@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::models::VideoInfo;
+
+use super::ffmpeg::command;
+
+// Subset of `ffprobe -print_format json -show_format -show_streams`
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    avg_frame_rate: Option<String>,
+    duration: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    format_name: Option<String>,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+// Read container and stream information with ffprobe
+pub fn probe_video(ffprobe: &Path, input: &Path) -> Result<VideoInfo, String> {
+    let output = command(ffprobe)
+        .args(["-v", "error", "-print_format", "json"])
+        .args(["-show_format", "-show_streams"])
+        .arg(input)
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let probe: ProbeOutput = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
+
+    let stream = |kind: &str| {
+        probe
+            .streams
+            .iter()
+            .find(|s| s.codec_type.as_deref() == Some(kind))
+    };
+    let video = stream("video");
+    let audio = stream("audio");
+    let format = probe.format.as_ref();
+
+    // Container duration, falling back to the video stream
+    let duration = format
+        .and_then(|f| f.duration.as_deref())
+        .or_else(|| video.and_then(|v| v.duration.as_deref()))
+        .and_then(|d| d.parse().ok());
+
+    Ok(VideoInfo {
+        format_name: format.and_then(|f| f.format_name.clone()),
+        duration,
+        width: video.and_then(|v| v.width),
+        height: video.and_then(|v| v.height),
+        frame_rate: video
+            .and_then(|v| v.avg_frame_rate.as_deref())
+            .and_then(parse_rate),
+        video_codec: video.and_then(|v| v.codec_name.clone()),
+        audio_codec: audio.and_then(|a| a.codec_name.clone()),
+        bit_rate: format
+            .and_then(|f| f.bit_rate.as_deref())
+            .and_then(|b| b.parse().ok()),
+    })
+}
+
+// Parse "30000/1001" style frame rate ("0/0" when unknown)
+fn parse_rate(rate: &str) -> Option<f64> {
+    let (numerator, denominator) = rate.split_once('/')?;
+    let numerator: f64 = numerator.parse().ok()?;
+    let denominator: f64 = denominator.parse().ok()?;
+
+    (denominator > 0.0 && numerator > 0.0).then(|| numerator / denominator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frame_rates() {
+        assert_eq!(parse_rate("25/1"), Some(25.0));
+        assert!((parse_rate("30000/1001").unwrap() - 29.97).abs() < 0.001);
+        // ffprobe reports unknown rates as 0/0
+        assert_eq!(parse_rate("0/0"), None);
+        assert_eq!(parse_rate("25/0"), None);
+        assert_eq!(parse_rate("25"), None);
+        assert_eq!(parse_rate("a/b"), None);
+        assert_eq!(parse_rate("NaN/1"), None);
+    }
+}
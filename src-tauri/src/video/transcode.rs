@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+
+use crate::models::VideoOptions;
+
+use super::ffmpeg::command;
+use super::jobs::{attach_process, detach_process, VideoJob};
+
+// How a transcode ended (errors are returned as Err)
+pub enum TranscodeOutcome {
+    Completed,
+    Cancelled,
+}
+
+// File extension written for a target format
+pub fn output_extension(target_format: &str) -> &str {
+    match target_format {
+        "apng" => "png",
+        format => format,
+    }
+}
+
+// Run ffmpeg, reporting progress (0.0 - 1.0) parsed from `-progress` output
+// The child process is registered in jobs under job_id so cancel_job can kill it
+// Partial output is removed on failure or cancellation
+#[allow(clippy::too_many_arguments)]
+pub fn transcode(
+    ffmpeg: &Path,
+    input: &Path,
+    output: &Path,
+    options: &VideoOptions,
+    input_duration: Option<f64>,
+    jobs: &Arc<Mutex<HashMap<String, VideoJob>>>,
+    job_id: &str,
+    mut on_progress: impl FnMut(f64),
+) -> Result<TranscodeOutcome, String> {
+    let mut cmd = command(ffmpeg);
+    cmd.args(["-hide_banner", "-nostdin", "-v", "error", "-nostats"])
+        .args(["-progress", "pipe:1", "-y"]);
+    if let Some(start) = options.start_time {
+        cmd.arg("-ss").arg(start.to_string());
+    }
+    cmd.arg("-i").arg(input);
+    if let Some(duration) = options.duration {
+        cmd.arg("-t").arg(duration.to_string());
+    }
+    cmd.args(output_args(options)?)
+        .arg(output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    let stdout = child.stdout.take().ok_or("Failed to read ffmpeg output")?;
+    let stderr = child.stderr.take().ok_or("Failed to read ffmpeg output")?;
+
+    // Drain stderr on its own thread so a full pipe cannot block ffmpeg
+    let stderr_reader = std::thread::spawn(move || {
+        let mut message = String::new();
+        let _ = BufReader::new(stderr).read_to_string(&mut message);
+        message
+    });
+
+    if !attach_process(jobs, job_id, child) {
+        let _ = stderr_reader.join();
+        let _ = std::fs::remove_file(output);
+        return Ok(TranscodeOutcome::Cancelled);
+    }
+
+    // Length of the converted range, used to turn out_time into a fraction
+    let total = options.duration.or_else(|| {
+        input_duration.map(|duration| (duration - options.start_time.unwrap_or(0.0)).max(0.0))
+    });
+
+    // Progress blocks are key=value lines, out_time_us is the output position
+    // (older ffmpeg versions only write out_time_ms, also in microseconds)
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        let position = line
+            .strip_prefix("out_time_us=")
+            .or_else(|| line.strip_prefix("out_time_ms="))
+            .and_then(|value| value.trim().parse::<f64>().ok());
+
+        if let (Some(position), Some(total)) = (position, total) {
+            if total > 0.0 {
+                on_progress((position / 1_000_000.0 / total).clamp(0.0, 1.0));
+            }
+        }
+    }
+
+    // stdout closes when ffmpeg exits or is killed
+    let child = detach_process(jobs, job_id);
+    let message = stderr_reader.join().unwrap_or_default();
+
+    // Process already taken: cancel_job killed it
+    let Some(mut child) = child else {
+        let _ = std::fs::remove_file(output);
+        return Ok(TranscodeOutcome::Cancelled);
+    };
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for ffmpeg: {}", e))?;
+    if !status.success() {
+        let _ = std::fs::remove_file(output);
+        let reason = message.lines().last().unwrap_or("unknown error").trim();
+        return Err(format!("ffmpeg failed ({}): {}", status, reason));
+    }
+
+    on_progress(1.0);
+    Ok(TranscodeOutcome::Completed)
+}
+
+// Encoder arguments for the target container / animated image format
+fn output_args(options: &VideoOptions) -> Result<Vec<String>, String> {
+    let mut filters = Vec::new();
+    if let Some(fps) = options.fps {
+        filters.push(format!("fps={}", fps));
+    }
+    if options.width.is_some() || options.height.is_some() {
+        // -2 keeps the aspect ratio with an even dimension (required by yuv420p)
+        let side = |value: Option<u32>| value.map_or("-2".to_string(), |v| v.to_string());
+        filters.push(format!(
+            "scale={}:{}:flags=lanczos",
+            side(options.width),
+            side(options.height)
+        ));
+    }
+
+    let mut args: Vec<String> = Vec::new();
+    let mut audio = !options.remove_audio;
+
+    match options.target_format.as_str() {
+        "gif" => {
+            // Palette generated from the clip instead of ffmpeg's fixed web palette
+            filters.push(
+                "split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=dither=sierra2_4a"
+                    .to_string(),
+            );
+            args.extend(["-loop", "0"].map(String::from));
+            audio = false;
+        }
+        "webp" => {
+            let quality = options.quality.unwrap_or(80).min(100);
+            args.extend(["-c:v", "libwebp_anim", "-loop", "0"].map(String::from));
+            args.extend(["-q:v".to_string(), quality.to_string()]);
+            audio = false;
+        }
+        "apng" => {
+            args.extend(["-f", "apng", "-plays", "0"].map(String::from));
+            audio = false;
+        }
+        "mp4" | "mov" | "mkv" => {
+            let codec = options.video_codec.as_deref().unwrap_or("libx264");
+            args.extend(["-c:v", codec, "-pix_fmt", "yuv420p"].map(String::from));
+            args.extend(["-crf".to_string(), options.crf.unwrap_or(23).to_string()]);
+            args.extend([
+                "-preset".to_string(),
+                options.preset.as_deref().unwrap_or("medium").to_string(),
+            ]);
+            if options.target_format != "mkv" {
+                // Index at the start so playback can begin before download finishes
+                args.extend(["-movflags", "+faststart"].map(String::from));
+            }
+            if audio {
+                let codec = options.audio_codec.as_deref().unwrap_or("aac");
+                args.extend(["-c:a", codec, "-b:a", "192k"].map(String::from));
+            }
+        }
+        "webm" => {
+            let codec = options.video_codec.as_deref().unwrap_or("libvpx-vp9");
+            args.extend(["-c:v", codec, "-b:v", "0"].map(String::from));
+            args.extend(["-crf".to_string(), options.crf.unwrap_or(32).to_string()]);
+            if audio {
+                let codec = options.audio_codec.as_deref().unwrap_or("libopus");
+                args.extend(["-c:a", codec].map(String::from));
+            }
+        }
+        "avi" => {
+            let codec = options.video_codec.as_deref().unwrap_or("mpeg4");
+            args.extend(["-c:v", codec, "-q:v", "3"].map(String::from));
+            if audio {
+                let codec = options.audio_codec.as_deref().unwrap_or("libmp3lame");
+                args.extend(["-c:a", codec].map(String::from));
+            }
+        }
+        format => return Err(format!("Unsupported video format: {}", format)),
+    }
+
+    if !filters.is_empty() {
+        args.extend(["-vf".to_string(), filters.join(",")]);
+    }
+    if !audio {
+        args.push("-an".to_string());
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(options: serde_json::Value) -> Vec<String> {
+        output_args(&serde_json::from_value(options).unwrap()).unwrap()
+    }
+
+    // Value following a flag, None when the flag is missing
+    fn value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        let index = args.iter().position(|arg| arg == flag)?;
+        args.get(index + 1).map(String::as_str)
+    }
+
+    #[test]
+    fn containers_use_default_codecs() {
+        let mp4 = args(json!({ "target_format": "mp4" }));
+        assert_eq!(value(&mp4, "-c:v"), Some("libx264"));
+        assert_eq!(value(&mp4, "-pix_fmt"), Some("yuv420p"));
+        assert_eq!(value(&mp4, "-crf"), Some("23"));
+        assert_eq!(value(&mp4, "-preset"), Some("medium"));
+        assert_eq!(value(&mp4, "-movflags"), Some("+faststart"));
+        assert_eq!(value(&mp4, "-c:a"), Some("aac"));
+
+        let mkv = args(json!({ "target_format": "mkv", "crf": 18, "preset": "slow" }));
+        assert_eq!(value(&mkv, "-crf"), Some("18"));
+        assert_eq!(value(&mkv, "-preset"), Some("slow"));
+        assert_eq!(value(&mkv, "-movflags"), None);
+
+        let webm = args(json!({ "target_format": "webm" }));
+        assert_eq!(value(&webm, "-c:v"), Some("libvpx-vp9"));
+        assert_eq!(value(&webm, "-crf"), Some("32"));
+        assert_eq!(value(&webm, "-c:a"), Some("libopus"));
+
+        let avi = args(json!({ "target_format": "avi", "video_codec": "mjpeg" }));
+        assert_eq!(value(&avi, "-c:v"), Some("mjpeg"));
+        assert_eq!(value(&avi, "-c:a"), Some("libmp3lame"));
+    }
+
+    #[test]
+    fn removed_audio_has_no_audio_codec() {
+        let mp4 = args(json!({ "target_format": "mp4", "remove_audio": true }));
+        assert_eq!(value(&mp4, "-c:a"), None);
+        assert!(mp4.contains(&"-an".to_string()));
+    }
+
+    #[test]
+    fn animated_images_drop_audio_and_loop() {
+        let gif = args(json!({ "target_format": "gif", "fps": 10.0, "width": 320 }));
+        assert_eq!(value(&gif, "-loop"), Some("0"));
+        let filters = value(&gif, "-vf").unwrap();
+        assert!(filters.starts_with("fps=10,scale=320:-2:flags=lanczos,split"));
+        assert!(filters.contains("palettegen"));
+
+        let webp = args(json!({ "target_format": "webp", "quality": 150 }));
+        assert_eq!(value(&webp, "-c:v"), Some("libwebp_anim"));
+        assert_eq!(value(&webp, "-q:v"), Some("100"));
+
+        let apng = args(json!({ "target_format": "apng", "height": 240 }));
+        assert_eq!(value(&apng, "-f"), Some("apng"));
+        assert_eq!(value(&apng, "-plays"), Some("0"));
+        assert_eq!(value(&apng, "-vf"), Some("scale=-2:240:flags=lanczos"));
+
+        for args in [gif, webp, apng] {
+            assert!(args.contains(&"-an".to_string()));
+            assert_eq!(value(&args, "-c:a"), None);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        let options = serde_json::from_value(json!({ "target_format": "flv" })).unwrap();
+        assert!(output_args(&options).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+// Round-trips every fixture through every output format and compares the
+// decoded result against the source as it would be displayed
+use std::fs;
+use std::path::Path;
+
+use anyimage_converter_lib::converters::{
+    encode_image, flatten_for_target, tone_map, tone_map_for_target,
+};
+use anyimage_converter_lib::decode::{decode_image, decode_source};
+use anyimage_converter_lib::metrics::psnr;
+use anyimage_converter_lib::models::{ConversionOptions, RawOptions};
+use image::{DynamicImage, GenericImageView};
+use serde_json::json;
+
+// Output format and minimum PSNR (dB) after the round trip
+// Lossless formats still lose precision when deep / float sources are dithered to 8 bits
+const OUTPUTS: &[(&str, f64)] = &[
+    ("png", 45.0),
+    ("tiff", 45.0),
+    ("bmp", 45.0),
+    ("tga", 45.0),
+    ("qoi", 45.0),
+    ("pnm", 45.0),
+    ("exr", 45.0),
+    ("hdr", 40.0),
+    ("jpeg", 28.0),
+    ("webp", 28.0),
+    ("avif", 28.0),
+    ("jxl", 28.0),
+    ("gif", 22.0),
+];
+
+fn options(target_format: &str) -> ConversionOptions {
+    serde_json::from_value(json!({
+        "target_format": target_format,
+        "quality": 90,
+        "avif_speed": 10,
+    }))
+    .unwrap()
+}
+
+// The source as the encoder saw it, tone mapped for display
+fn reference(source: &DynamicImage, options: &ConversionOptions) -> DynamicImage {
+    let tone_mapped = tone_map_for_target(source, &options.target_format);
+    let img = tone_mapped.as_ref().unwrap_or(source);
+    let flattened = flatten_for_target(img, options);
+    let img = flattened.as_ref().unwrap_or(img);
+    let img = tone_map(img).unwrap_or_else(|| img.clone());
+
+    // GIF keeps only fully transparent or opaque pixels
+    if options.target_format != "gif" || !img.has_alpha() {
+        return img;
+    }
+    let threshold = u16::from(options.gif.alpha_threshold) * 257;
+    let mut rgba = img.to_rgba16();
+    for pixel in rgba.pixels_mut() {
+        pixel[3] = if pixel[3] >= threshold { u16::MAX } else { 0 };
+    }
+    DynamicImage::ImageRgba16(rgba)
+}
+
+// Composite over white so transparent pixels compare by coverage, not hidden color
+fn composite(img: DynamicImage) -> DynamicImage {
+    let mut options = options("png");
+    options.flatten_alpha = true;
+    flatten_for_target(&img, &options).unwrap_or(img)
+}
+
+#[test]
+fn every_input_round_trips_through_every_output() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "no fixtures in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for path in &fixtures {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let data = fs::read(path).unwrap();
+        let source = match decode_source(&data, &RawOptions::default()) {
+            Ok(img) => img,
+            Err(e) => {
+                failures.push(format!("{}: decode failed: {}", name, e));
+                continue;
+            }
+        };
+
+        for &(format, min_psnr) in OUTPUTS {
+            let options = options(format);
+            let result = encode_image(&source, &options, None)
+                .and_then(|encoded| decode_image(&encoded.data));
+            let decoded = match result {
+                Ok(img) => img,
+                Err(e) => {
+                    failures.push(format!("{} -> {}: {}", name, format, e));
+                    continue;
+                }
+            };
+            if decoded.dimensions() != source.dimensions() {
+                failures.push(format!(
+                    "{} -> {}: decoded as {:?}, expected {:?}",
+                    name,
+                    format,
+                    decoded.dimensions(),
+                    source.dimensions()
+                ));
+                continue;
+            }
+
+            let expected = composite(reference(&source, &options));
+            let actual = composite(tone_map(&decoded).unwrap_or(decoded));
+            match psnr(&expected, &actual) {
+                Ok(value) if value >= min_psnr => {}
+                Ok(value) => failures.push(format!(
+                    "{} -> {}: PSNR {:.1} dB, expected at least {:.1}",
+                    name, format, value, min_psnr
+                )),
+                Err(e) => failures.push(format!("{} -> {}: {}", name, format, e)),
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,59 @@
+// Multi-page TIFFs with color types the tiff crate doesn't expand itself
+use std::fs;
+use std::path::Path;
+
+use anyimage_converter_lib::decode::decode_pages;
+use anyimage_converter_lib::models::RawOptions;
+use image::DynamicImage;
+
+fn pages(name: &str) -> Vec<DynamicImage> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/pages")
+        .join(name);
+    decode_pages(&fs::read(path).unwrap(), &RawOptions::default()).unwrap()
+}
+
+#[test]
+fn bilevel_pages_are_unpacked() {
+    // BlackIsZero page, then the same pixels stored as WhiteIsZero
+    let pages = pages("bilevel.tif");
+    assert_eq!(pages.len(), 2);
+
+    for page in &pages {
+        let gray = page.as_luma8().expect("bilevel pages decode as 8 bit gray");
+        assert_eq!(gray.dimensions(), (10, 3));
+        for (x, y, pixel) in gray.enumerate_pixels() {
+            let expected = if (x + y) % 3 == 0 { 255 } else { 0 };
+            assert_eq!(pixel.0, [expected], "pixel {},{}", x, y);
+        }
+    }
+}
+
+#[test]
+fn palette_pages_are_expanded_through_the_color_map() {
+    // Uncompressed 4 bit page, Deflate and PackBits 8 bit pages
+    let pages = pages("palette.tif");
+    assert_eq!(pages.len(), 3);
+    let color = |index: u8| [index, 255 - index, index.wrapping_mul(7)];
+
+    let four_bit = pages[0].as_rgb8().expect("palette pages decode as RGB");
+    assert_eq!(four_bit.dimensions(), (5, 2));
+    for (x, y, pixel) in four_bit.enumerate_pixels() {
+        let index = ((x + 2 * y) % 16) as u8;
+        assert_eq!(pixel.0, [index * 17, (15 - index) * 17, 128]);
+    }
+
+    let deflate = pages[1].as_rgb8().expect("palette pages decode as RGB");
+    assert_eq!(deflate.dimensions(), (3, 2));
+    for (x, y, pixel) in deflate.enumerate_pixels() {
+        assert_eq!(pixel.0, color((x * 50 + y) as u8));
+    }
+
+    // One run of 7 then two literal bytes
+    let packbits = pages[2].as_rgb8().expect("palette pages decode as RGB");
+    let indices = [7, 7, 7, 7, 9, 10];
+    assert!(packbits
+        .pixels()
+        .zip(indices)
+        .all(|(pixel, index)| pixel.0 == color(index)));
+}
@@ -333,10 +333,12 @@ export function Footer() {
                 const results = await invoke<ConversionResult[]>(
                   "convert_images",
                   {
-                    targetFormat,
-                    quality,
-                    avifSpeed,
-                    preserveExif,
+                    options: {
+                      target_format: targetFormat,
+                      quality,
+                      avif_speed: avifSpeed,
+                      preserve_exif: preserveExif,
+                    },
                     preserveTimestamps,
                     outputDir,
                     maxConcurrent: maxConcurrentConversions,